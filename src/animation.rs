@@ -0,0 +1,336 @@
+use std::path::Path;
+use crate::tuples::*;
+use crate::quaternion::*;
+use crate::transformations::*;
+use crate::geometry::*;
+use crate::image::*;
+use crate::light::*;
+use crate::camera::*;
+use crate::raytracer::*;
+
+pub trait Lerp {
+    fn lerp(&self, other: &Self, t: f64) -> Self;
+}
+
+impl Lerp for f64 {
+    fn lerp(&self, other: &f64, t: f64) -> f64 {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for Point3 {
+    fn lerp(&self, other: &Point3, t: f64) -> Point3 {
+        *self + (*other - *self) * t
+    }
+}
+
+impl Lerp for Vector3 {
+    fn lerp(&self, other: &Vector3, t: f64) -> Vector3 {
+        *self + (*other - *self) * t
+    }
+}
+
+impl Lerp for Quaternion {
+    fn lerp(&self, other: &Quaternion, t: f64) -> Quaternion {
+        self.slerp(other, t)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    EaseInOut,
+    // cubic bezier timing curve through (0, 0), (x1, y1), (x2, y2), (1, 1)
+    Bezier { x1: f64, y1: f64, x2: f64, y2: f64 }
+}
+
+impl Interpolation {
+
+    pub fn ease(&self, t: f64) -> f64 {
+        match *self {
+            Interpolation::Step => 0.0,
+            Interpolation::Linear => t,
+            Interpolation::EaseInOut => Interpolation::bezier(0.42, 0.0, 0.58, 1.0, t),
+            Interpolation::Bezier { x1, y1, x2, y2 } => Interpolation::bezier(x1, y1, x2, y2, t)
+        }
+    }
+
+    fn bezier(x1: f64, y1: f64, x2: f64, y2: f64, t: f64) -> f64 {
+        let cubic = |a: f64, b: f64, s: f64| {
+            let u = 1.0 - s;
+            3.0 * u * u * s * a + 3.0 * u * s * s * b + s * s * s
+        };
+        // x(s) is monotonic for control points in [0, 1], so bisect for x(s) = t
+        let mut lo = 0.0;
+        let mut hi = 1.0;
+        for _ in 0 .. 50 {
+            let mid = (lo + hi) / 2.0;
+            if cubic(x1, x2, mid) < t {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        cubic(y1, y2, (lo + hi) / 2.0)
+    }
+
+}
+
+#[derive(Clone, Debug)]
+pub struct Key<T> {
+    pub frame: f64,
+    pub value: T,
+    // interpolation towards the next key
+    pub interpolation: Interpolation
+}
+
+impl <T> Key<T> {
+    pub fn new(frame: f64, value: T, interpolation: Interpolation) -> Key<T> {
+        Key {frame, value, interpolation}
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Curve<T> {
+    keys: Vec<Key<T>>
+}
+
+impl <T: Lerp + Clone> Curve<T> {
+
+    pub fn new(keys: Vec<Key<T>>) -> Curve<T> {
+        assert!(!keys.is_empty());
+        let mut keys = keys;
+        keys.sort_by(|a, b| a.frame.partial_cmp(&b.frame).unwrap());
+        Curve {keys}
+    }
+
+    pub fn constant(value: T) -> Curve<T> {
+        Curve::new(vec![Key::new(0.0, value, Interpolation::Step)])
+    }
+
+    pub fn at(&self, frame: f64) -> T {
+        let first = &self.keys[0];
+        let last = &self.keys[self.keys.len() - 1];
+        if frame <= first.frame {
+            return first.value.clone();
+        }
+        if frame >= last.frame {
+            return last.value.clone();
+        }
+        let next = self.keys.iter().position(|k| k.frame > frame).unwrap();
+        let a = &self.keys[next - 1];
+        let b = &self.keys[next];
+        let t = (frame - a.frame) / (b.frame - a.frame);
+        a.value.lerp(&b.value, a.interpolation.ease(t))
+    }
+
+}
+
+pub struct TransformCurve {
+    pub translation: Curve<Vector3>,
+    pub rotation: Curve<Quaternion>,
+    pub scale: Curve<Vector3>
+}
+
+impl TransformCurve {
+
+    pub fn new(translation: Curve<Vector3>, rotation: Curve<Quaternion>, scale: Curve<Vector3>) -> TransformCurve {
+        TransformCurve {translation, rotation, scale}
+    }
+
+    pub fn identity() -> TransformCurve {
+        TransformCurve::new(
+            Curve::constant(Vector3::new(0.0, 0.0, 0.0)),
+            Curve::constant(Quaternion::identity()),
+            Curve::constant(Vector3::new(1.0, 1.0, 1.0))
+        )
+    }
+
+    pub fn at(&self, frame: f64) -> Transformation {
+        Keyframe::new(frame, self.translation.at(frame), self.rotation.at(frame), self.scale.at(frame)).transformation()
+    }
+
+}
+
+pub struct AnimatedCamera {
+    pub hsize: f64,
+    pub vsize: f64,
+    pub from: Curve<Point3>,
+    pub to: Curve<Point3>,
+    pub up: Curve<Vector3>,
+    pub fov: Curve<f64>
+}
+
+impl AnimatedCamera {
+
+    pub fn new(hsize: f64, vsize: f64, from: Curve<Point3>, to: Curve<Point3>, up: Curve<Vector3>, fov: Curve<f64>) -> AnimatedCamera {
+        AnimatedCamera {hsize, vsize, from, to, up, fov}
+    }
+
+    pub fn at(&self, frame: f64) -> Camera {
+        let view = Transformation::view(self.from.at(frame), self.to.at(frame), self.up.at(frame));
+        Camera::new(self.hsize, self.vsize, self.fov.at(frame), view)
+    }
+
+}
+
+pub struct AnimatedLight {
+    pub color: Color,
+    pub pos: Curve<Point3>
+}
+
+impl AnimatedLight {
+
+    pub fn new(color: Color, pos: Curve<Point3>) -> AnimatedLight {
+        AnimatedLight {color, pos}
+    }
+
+    pub fn at(&self, frame: f64) -> PointLight {
+        PointLight::new(self.color.clone(), self.pos.at(frame))
+    }
+
+}
+
+type ShapeBuilder = Box<dyn Fn(Transformation) -> Box<dyn Primitive>>;
+
+pub struct AnimatedObject {
+    motion: TransformCurve,
+    shape: ShapeBuilder
+}
+
+impl AnimatedObject {
+
+    // the builder receives the animated transformation and is free to
+    // chain its own static placement onto it
+    pub fn new<F: Fn(Transformation) -> Box<dyn Primitive> + 'static>(motion: TransformCurve, shape: F) -> AnimatedObject {
+        AnimatedObject {motion, shape: Box::new(shape)}
+    }
+
+    pub fn fixed<F: Fn(Transformation) -> Box<dyn Primitive> + 'static>(shape: F) -> AnimatedObject {
+        AnimatedObject::new(TransformCurve::identity(), shape)
+    }
+
+    pub fn at(&self, frame: f64) -> Box<dyn Primitive> {
+        (self.shape)(self.motion.at(frame))
+    }
+
+}
+
+pub struct AnimatedScene {
+    pub camera: AnimatedCamera,
+    pub lights: Vec<AnimatedLight>,
+    pub objects: Vec<AnimatedObject>
+}
+
+impl AnimatedScene {
+
+    pub fn new(camera: AnimatedCamera, lights: Vec<AnimatedLight>, objects: Vec<AnimatedObject>) -> AnimatedScene {
+        AnimatedScene {camera, lights, objects}
+    }
+
+    pub fn frame(&self, frame: f64) -> RayTracer {
+        let objects = self.objects.iter().map(|o| o.at(frame)).collect();
+        let lights = self.lights.iter().map(|l| l.at(frame)).collect();
+        RayTracer::new(self.camera.at(frame), World::new(objects, lights))
+    }
+
+}
+
+// replaces the run of '#' in the pattern by the zero padded frame number,
+// "turntable_####.png" becomes "turntable_0042.png"
+pub fn frame_path(pattern: &str, frame: usize) -> String {
+    match pattern.find('#') {
+        None => {
+            // only the file name gets the number, dots in the directory stay
+            let path = Path::new(pattern);
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+            let name = match path.extension().and_then(|e| e.to_str()) {
+                Some(ext) => format!("{}_{:04}.{}", stem, frame, ext),
+                None => format!("{}_{:04}", stem, frame)
+            };
+            path.with_file_name(name).to_string_lossy().into_owned()
+        },
+        Some(start) => {
+            let width = pattern[start ..].chars().take_while(|c| *c == '#').count();
+            format!("{}{:0width$}{}", &pattern[.. start], frame, &pattern[start + width ..], width = width)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn frame_paths() {
+        assert_eq!(frame_path("turntable_####.png", 42), "turntable_0042.png");
+        assert_eq!(frame_path("f#.png", 7), "f7.png");
+        assert_eq!(frame_path("turntable.png", 3), "turntable_0003.png");
+        assert_eq!(frame_path("./renders/turntable", 3), "./renders/turntable_0003");
+        assert_eq!(frame_path("out.d/frame.ppm", 12), "out.d/frame_0012.ppm");
+    }
+
+    #[test]
+    fn curve_keys_and_clamping() {
+        let curve = Curve::new(vec![
+            Key::new(10.0, 4.0, Interpolation::Linear),
+            Key::new(0.0, 2.0, Interpolation::Linear),
+            Key::new(20.0, 0.0, Interpolation::Linear)
+        ]);
+        assert!(close(curve.at(0.0), 2.0));
+        assert!(close(curve.at(5.0), 3.0));
+        assert!(close(curve.at(10.0), 4.0));
+        assert!(close(curve.at(15.0), 2.0));
+        assert!(close(curve.at(20.0), 0.0));
+        assert!(close(curve.at(-3.0), 2.0));
+        assert!(close(curve.at(100.0), 0.0));
+        assert!(close(Curve::constant(1.5).at(-8.0), 1.5));
+    }
+
+    #[test]
+    fn interpolations() {
+        let step = Curve::new(vec![Key::new(0.0, 0.0, Interpolation::Step), Key::new(1.0, 1.0, Interpolation::Step)]);
+        assert!(close(step.at(0.99), 0.0));
+        assert!(close(step.at(1.0), 1.0));
+        let curves = [
+            Interpolation::Linear,
+            Interpolation::EaseInOut,
+            Interpolation::Bezier { x1: 0.25, y1: 0.1, x2: 0.25, y2: 1.0 }
+        ];
+        for i in curves.iter() {
+            assert!(close(i.ease(0.0), 0.0));
+            assert!(close(i.ease(1.0), 1.0));
+        }
+        // ease in out is symmetric and slower than linear at the start
+        let ease = Interpolation::EaseInOut;
+        assert!(close(ease.ease(0.5), 0.5));
+        assert!(ease.ease(0.1) < 0.1);
+        assert!(close(ease.ease(0.2) + ease.ease(0.8), 1.0));
+        // control points on the diagonal give a straight line
+        let straight = Interpolation::Bezier { x1: 0.25, y1: 0.25, x2: 0.75, y2: 0.75 };
+        assert!(close(straight.ease(0.3), 0.3));
+    }
+
+    #[test]
+    fn transform_curve() {
+        let curve = TransformCurve::new(
+            Curve::new(vec![Key::new(0.0, Vector3::new(0.0, 0.0, 0.0), Interpolation::Linear), Key::new(10.0, Vector3::new(10.0, 0.0, 0.0), Interpolation::Linear)]),
+            Curve::new(vec![Key::new(0.0, Quaternion::identity(), Interpolation::Linear), Key::new(10.0, Quaternion::from_axis_angle(&Vector3::new(0.0, 0.0, 1.0), 180.0), Interpolation::Linear)]),
+            Curve::constant(Vector3::new(2.0, 2.0, 2.0))
+        );
+        let p = Point3::new(1.0, 0.0, 0.0);
+        // halfway: scaled by 2, turned a quarter around z, then moved 5 along x
+        let q = curve.at(5.0).transform(&p);
+        assert!(close(q.x, 5.0) && close(q.y, 2.0) && close(q.z, 0.0), "{:?}", q);
+        let q = curve.at(20.0).transform(&p);
+        assert!(close(q.x, 8.0) && close(q.y, 0.0) && close(q.z, 0.0), "{:?}", q);
+        let q = TransformCurve::identity().at(3.0).transform(&p);
+        assert!(close(q.x, 1.0) && close(q.y, 0.0) && close(q.z, 0.0), "{:?}", q);
+    }
+
+}
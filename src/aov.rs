@@ -0,0 +1,289 @@
+use std::io;
+use std::path::Path;
+use crate::image::*;
+use crate::exr;
+use crate::exr::{ExrPixel, ExrCompression};
+use crate::tuples::*;
+use crate::tonemap::{ToneMap, ToneMapping};
+use crate::colorspace::Transfer;
+
+// arbitrary output variables written next to the beauty image, pixels
+// without a hit have zero alpha in every pass
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pass {
+    // distance from the camera along the ray
+    Depth,
+    // world space, components in [-1, 1] or as n * 0.5 + 0.5 in formats
+    // that clip to [0, 1]
+    Normal,
+    // material color
+    Albedo,
+    ObjectId,
+    // ambient and diffuse light, diffuse + specular + reflection = beauty
+    Diffuse,
+    Specular,
+    Reflection,
+    // fraction of the lights that are blocked
+    Shadow
+}
+
+pub const ALL_PASSES: [Pass; 8] = [
+    Pass::Depth, Pass::Normal, Pass::Albedo, Pass::ObjectId,
+    Pass::Diffuse, Pass::Specular, Pass::Reflection, Pass::Shadow
+];
+
+impl Pass {
+
+    pub fn parse(name: &str) -> Option<Pass> {
+        ALL_PASSES.iter().find(|p| p.name() == name).copied()
+    }
+
+    // accepts a comma separated list or all
+    pub fn parse_list(names: &str) -> Option<Vec<Pass>> {
+        if names == "all" {
+            return Some(ALL_PASSES.to_vec());
+        }
+        names.split(',').map(Pass::parse).collect()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Pass::Depth => "depth",
+            Pass::Normal => "normal",
+            Pass::Albedo => "albedo",
+            Pass::ObjectId => "id",
+            Pass::Diffuse => "diffuse",
+            Pass::Specular => "specular",
+            Pass::Reflection => "reflection",
+            Pass::Shadow => "shadow"
+        }
+    }
+
+    // passes holding measurements rather than light are neither exposed nor
+    // gamma encoded
+    pub fn is_data(&self) -> bool {
+        matches!(*self, Pass::Depth | Pass::Normal | Pass::ObjectId | Pass::Shadow)
+    }
+
+    // one value per pixel, stored in all three channels of the image
+    pub fn is_scalar(&self) -> bool {
+        matches!(*self, Pass::Depth | Pass::ObjectId | Pass::Shadow)
+    }
+
+}
+
+// what a camera ray found at its first hit
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub color: Color,
+    pub hit: bool,
+    pub depth: f64,
+    pub normal: Normal3,
+    pub albedo: Color,
+    pub object_id: usize,
+    pub diffuse: Color,
+    pub specular: Color,
+    pub reflection: Color,
+    pub shadow: f64
+}
+
+impl Sample {
+
+    pub fn miss(color: Color) -> Sample {
+        Sample {
+            color,
+            hit: false,
+            depth: 0.0,
+            normal: Normal3::new(0.0, 0.0, 0.0),
+            albedo: Color::black(),
+            object_id: 0,
+            diffuse: Color::black(),
+            specular: Color::black(),
+            reflection: Color::black(),
+            shadow: 0.0
+        }
+    }
+
+    pub fn pass(&self, pass: Pass) -> Color {
+        let scalar = |v: f64| Color::new(v, v, v);
+        let mut c = match pass {
+            Pass::Depth => scalar(self.depth),
+            Pass::Normal => Color::new(self.normal.x, self.normal.y, self.normal.z),
+            Pass::Albedo => self.albedo.clone(),
+            Pass::ObjectId => scalar(self.object_id as f64),
+            Pass::Diffuse => self.diffuse.clone(),
+            Pass::Specular => self.specular.clone(),
+            Pass::Reflection => self.reflection.clone(),
+            Pass::Shadow => scalar(self.shadow)
+        };
+        c.a = if self.hit { 1.0 } else { 0.0 };
+        c
+    }
+
+}
+
+// the beauty image and the requested passes, all of the same size
+#[derive(Clone)]
+pub struct RenderPasses {
+    pub beauty: Image,
+    pub passes: Vec<(Pass, Image)>
+}
+
+impl RenderPasses {
+
+    pub fn new(w: usize, h: usize, passes: &[Pass]) -> RenderPasses {
+        let passes = passes.iter()
+            .map(|p| {
+                let mut img = Image::new_rgba(w, h);
+                if p.is_data() {
+                    img.tone_mapping = ToneMapping::new(0.0, ToneMap::Clip, Transfer::Linear);
+                }
+                (*p, img)
+            })
+            .collect();
+        RenderPasses {beauty: Image::new_rgba(w, h), passes}
+    }
+
+    pub fn pass(&self, pass: Pass) -> Option<&Image> {
+        self.passes.iter().find(|(p, _)| *p == pass).map(|(_, img)| img)
+    }
+
+    pub fn set_tone_mapping(&mut self, tone_mapping: &ToneMapping) {
+        self.beauty.tone_mapping = tone_mapping.clone();
+        for (pass, img) in self.passes.iter_mut() {
+            if !pass.is_data() {
+                img.tone_mapping = tone_mapping.clone();
+            }
+        }
+    }
+
+    // beauty.exr becomes beauty.depth.exr, beauty.normal.exr and so on
+    pub fn pass_path(file: &str, pass: Pass) -> String {
+        let path = Path::new(file);
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) => path.with_extension(format!("{}.{}", pass.name(), ext)).to_string_lossy().into_owned(),
+            None => format!("{}.{}", file, pass.name())
+        }
+    }
+
+    // the passes are written in the format of the beauty image, variants
+    // like "scene.16.png" included, depth and object ids only fit into
+    // float formats
+    pub fn write_separate(&self, file: &str) -> io::Result<()> {
+        let format = ImageFormat::from_path(file)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("unknown image format: {}", file)))?;
+        if !format.is_float() {
+            if let Some(pass) = self.passes.iter().map(|(p, _)| p).find(|p| matches!(p, Pass::Depth | Pass::ObjectId)) {
+                let message = format!("the {} pass needs a pfm, hdr or exr file, not {}", pass.name(), file);
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+            }
+        }
+        self.beauty.write_as(file, format)?;
+        for (pass, img) in self.passes.iter() {
+            let path = RenderPasses::pass_path(file, *pass);
+            if *pass == Pass::Normal && !format.is_float() {
+                let mut encoded = img.clone();
+                for (_, _, c) in encoded.pixels_mut() {
+                    *c = Color {r: c.r * 0.5 + 0.5, g: c.g * 0.5 + 0.5, b: c.b * 0.5 + 0.5, ..c.clone()};
+                }
+                encoded.write_as(&path, format)?;
+            } else {
+                img.write_as(&path, format)?;
+            }
+        }
+        Ok(())
+    }
+
+    // beauty as R, G, B and every pass as a layer of its own, depth and
+    // object ids always in full float
+    pub fn write_layered(&self, file: &str, pixel_type: ExrPixel, compression: ExrCompression) -> io::Result<()> {
+        let rgb = |prefix: &str, img, pixel_type| -> Vec<exr::Channel> {
+            vec![
+                exr::Channel::new(&format!("{}B", prefix), img, |c| c.b, pixel_type),
+                exr::Channel::new(&format!("{}G", prefix), img, |c| c.g, pixel_type),
+                exr::Channel::new(&format!("{}R", prefix), img, |c| c.r, pixel_type)
+            ]
+        };
+        let mut channels = rgb("", &self.beauty, pixel_type);
+        channels.push(exr::Channel::new("A", &self.beauty, |c| c.a, pixel_type));
+        for (pass, img) in self.passes.iter() {
+            let prefix = format!("{}.", pass.name());
+            if pass.is_scalar() {
+                let precision = if *pass == Pass::Shadow { pixel_type } else { ExrPixel::Float };
+                channels.push(exr::Channel::new(&format!("{}Y", prefix), img, |c| c.r, precision));
+            } else {
+                channels.extend(rgb(&prefix, img, pixel_type));
+            }
+        }
+        exr::write_channels(file, self.beauty.w, self.beauty.h, channels, compression)
+    }
+
+    // layered when writing exr, one file per pass for everything else
+    pub fn write(&self, file: &str) -> io::Result<()> {
+        match ImageFormat::from_path(file) {
+            Some(ImageFormat::Exr(pixel_type, compression)) if !self.passes.is_empty() =>
+                self.write_layered(file, pixel_type, compression),
+            _ => self.write_separate(file)
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenes;
+
+    #[test]
+    fn light_passes_add_up_to_beauty() {
+        let passes = [Pass::Diffuse, Pass::Specular, Pass::Reflection];
+        let out = scenes::reflections(24.0, 16.0).trace_passes(3, &passes);
+        for (x, y, c) in out.beauty.pixels() {
+            let sum = passes.iter()
+                .map(|p| out.pass(*p).unwrap().at(x, y))
+                .fold(Color::black(), |acc, p| acc.add(&p));
+            assert!((sum.r - c.r).abs() < 1e-9 && (sum.g - c.g).abs() < 1e-9 && (sum.b - c.b).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn normals_fit_into_ldr_files() {
+        let out = scenes::default_world(16.0, 16.0).trace_passes(1, &[Pass::Normal]);
+        let file = std::env::temp_dir().join(format!("raytracer_normals_{}.p3.ppm", std::process::id()));
+        let file = file.to_str().unwrap();
+        out.write(file).unwrap();
+        let normal_file = RenderPasses::pass_path(file, Pass::Normal);
+        let text = std::fs::read_to_string(&normal_file).unwrap();
+        std::fs::remove_file(file).unwrap();
+        std::fs::remove_file(&normal_file).unwrap();
+        // past the P3, size and maximum, three values per pixel
+        let values: Vec<f64> = text.split_whitespace().skip(4).map(|v| v.parse::<f64>().unwrap() / 255.0).collect();
+        let n = out.pass(Pass::Normal).unwrap().at(8, 4);
+        let i = 3 * (4 * 16 + 8);
+        assert!(n.g > 0.3 && n.b < -0.5);
+        for (written, n) in values[i .. i + 3].iter().zip([n.r, n.g, n.b].iter()) {
+            assert!((written - (n * 0.5 + 0.5)).abs() < 0.01, "{} {}", written, n);
+        }
+    }
+
+    #[test]
+    fn depth_and_ids_need_float_files() {
+        let out = scenes::default_world(4.0, 4.0).trace_passes(1, &[Pass::Normal, Pass::ObjectId]);
+        let file = std::env::temp_dir().join(format!("raytracer_ids_{}.png", std::process::id()));
+        let file = file.to_str().unwrap();
+        let err = out.write(file).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!Path::new(file).exists());
+        assert!(!Path::new(&RenderPasses::pass_path(file, Pass::Normal)).exists());
+    }
+
+    #[test]
+    fn misses_have_no_coverage() {
+        let out = scenes::default_world(16.0, 16.0).trace_passes(1, &[Pass::Depth, Pass::ObjectId]);
+        let depth = out.pass(Pass::Depth).unwrap();
+        assert_eq!(depth.at(0, 0).a, 0.0);
+        assert_eq!(depth.at(8, 8).a, 1.0);
+        assert!((depth.at(8, 8).r - 4.0).abs() < 0.1);
+    }
+
+}
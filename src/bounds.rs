@@ -0,0 +1,132 @@
+use crate::tuples::*;
+use crate::transformations::Transformation;
+use crate::geometry::Ray;
+
+// axis aligned box, empty when min lies above max on any axis and infinite
+// for shapes without an end like planes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: Point3,
+    pub max: Point3
+}
+
+impl Bounds {
+
+    pub fn new(min: Point3, max: Point3) -> Bounds {
+        Bounds {min, max}
+    }
+
+    pub fn empty() -> Bounds {
+        let inf = f64::INFINITY;
+        Bounds::new(Point3::new(inf, inf, inf), Point3::new(-inf, -inf, -inf))
+    }
+
+    pub fn infinite() -> Bounds {
+        let inf = f64::INFINITY;
+        Bounds::new(Point3::new(-inf, -inf, -inf), Point3::new(inf, inf, inf))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn is_finite(&self) -> bool {
+        [self.min.x, self.min.y, self.min.z, self.max.x, self.max.y, self.max.z].iter().all(|v| v.is_finite())
+    }
+
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds::new(
+            Point3::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)),
+            Point3::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z))
+        )
+    }
+
+    pub fn intersection(&self, other: &Bounds) -> Bounds {
+        Bounds::new(
+            Point3::new(self.min.x.max(other.min.x), self.min.y.max(other.min.y), self.min.z.max(other.min.z)),
+            Point3::new(self.max.x.min(other.max.x), self.max.y.min(other.max.y), self.max.z.min(other.max.z))
+        )
+    }
+
+    // the box around the transformed corners, rotations make it grow
+    pub fn transform(&self, transformation: &Transformation) -> Bounds {
+        if self.is_empty() || !self.is_finite() {
+            return if self.is_empty() { Bounds::empty() } else { Bounds::infinite() };
+        }
+        let mut out = Bounds::empty();
+        for i in 0 .. 8 {
+            let corner = Point3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z }
+            );
+            let p = transformation.transform(&corner);
+            out = out.union(&Bounds::new(p, p));
+        }
+        out
+    }
+
+    // whether the line of the ray passes through the box, hits behind the
+    // origin count as well since csg keeps track of every crossing
+    pub fn hit(&self, ray: &Ray) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        if !self.is_finite() {
+            return true;
+        }
+        let mut near = f64::NEG_INFINITY;
+        let mut far = f64::INFINITY;
+        let axes = [
+            (ray.origin.x, ray.dir.x, self.min.x, self.max.x),
+            (ray.origin.y, ray.dir.y, self.min.y, self.max.y),
+            (ray.origin.z, ray.dir.z, self.min.z, self.max.z)
+        ];
+        for (origin, dir, min, max) in axes.iter() {
+            if dir.abs() < 1e-12 {
+                if origin < min || origin > max {
+                    return false;
+                }
+                continue;
+            }
+            let (t0, t1) = ((min - origin) / dir, (max - origin) / dir);
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        near <= far
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit() -> Bounds {
+        Bounds::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn transformed_box() {
+        let moved = unit().transform(&Transformation::translation(Vector3::new(5.0, 0.0, 0.0)));
+        assert_eq!(moved, Bounds::new(Point3::new(4.0, -1.0, -1.0), Point3::new(6.0, 1.0, 1.0)));
+        let turned = unit().transform(&Transformation::rotate_y(45.0));
+        assert!((turned.max.x - f64::sqrt(2.0)).abs() < 1e-9 && (turned.max.y - 1.0).abs() < 1e-9);
+        assert_eq!(Bounds::infinite().transform(&Transformation::rotate_y(45.0)), Bounds::infinite());
+    }
+
+    #[test]
+    fn ray_hits_box() {
+        let along_z = Ray::new(Point3::new(0.5, 0.5, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert!(unit().hit(&along_z));
+        // boxes behind the ray still count
+        let away = Ray::new(Point3::new(0.5, 0.5, -5.0), Vector3::new(0.0, 0.0, -1.0));
+        assert!(unit().hit(&away));
+        let beside = Ray::new(Point3::new(2.0, 0.5, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert!(!unit().hit(&beside));
+        let diagonal = Ray::new(Point3::new(-5.0, -5.0, -5.0), Vector3::new(1.0, 1.0, 1.0));
+        assert!(unit().hit(&diagonal));
+        assert!(!Bounds::empty().hit(&diagonal));
+    }
+
+}
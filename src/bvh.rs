@@ -0,0 +1,123 @@
+use crate::bounds::Bounds;
+use crate::geometry::Ray;
+
+// leaves hold at most this many items
+const LEAF_SIZE: usize = 4;
+
+enum Node {
+    Leaf(Vec<usize>),
+    // the nodes of both halves
+    Inner(usize, usize)
+}
+
+// bounding volume hierarchy over the boxes of a list of items, a ray only
+// visits the items whose boxes its line passes through, items without an
+// end like planes are visited by every ray and empty ones by none
+pub struct Bvh {
+    boxes: Vec<Bounds>,
+    // the root comes first
+    nodes: Vec<(Bounds, Node)>,
+    unbounded: Vec<usize>
+}
+
+fn center(b: &Bounds, axis: usize) -> f64 {
+    match axis {
+        0 => b.min.x + b.max.x,
+        1 => b.min.y + b.max.y,
+        _ => b.min.z + b.max.z
+    }
+}
+
+// splits at the median center along the axis the centers spread most,
+// returns the index of the node
+fn build(boxes: &[Bounds], mut items: Vec<usize>, nodes: &mut Vec<(Bounds, Node)>) -> usize {
+    let around = items.iter().fold(Bounds::empty(), |b, &i| b.union(&boxes[i]));
+    let index = nodes.len();
+    if items.len() <= LEAF_SIZE {
+        nodes.push((around, Node::Leaf(items)));
+        return index;
+    }
+    let spread = |axis: usize| {
+        let centers = items.iter().map(|&i| center(&boxes[i], axis));
+        centers.clone().fold(f64::NEG_INFINITY, f64::max) - centers.fold(f64::INFINITY, f64::min)
+    };
+    let axis = (0 .. 3).max_by(|&a, &b| spread(a).partial_cmp(&spread(b)).unwrap()).unwrap();
+    items.sort_by(|&a, &b| center(&boxes[a], axis).partial_cmp(&center(&boxes[b], axis)).unwrap());
+    let upper = items.split_off(items.len() / 2);
+    nodes.push((around, Node::Inner(0, 0)));
+    let left = build(boxes, items, nodes);
+    let right = build(boxes, upper, nodes);
+    nodes[index].1 = Node::Inner(left, right);
+    index
+}
+
+impl Bvh {
+
+    // built once over all the items, there is no adding to it later
+    pub fn new(boxes: Vec<Bounds>) -> Bvh {
+        let mut nodes = vec![];
+        let mut unbounded = vec![];
+        let mut bounded = vec![];
+        for (i, b) in boxes.iter().enumerate() {
+            if b.is_empty() {
+                continue;
+            }
+            if b.is_finite() { bounded.push(i) } else { unbounded.push(i) }
+        }
+        if !bounded.is_empty() {
+            build(&boxes, bounded, &mut nodes);
+        }
+        Bvh {boxes, nodes, unbounded}
+    }
+
+    // the items the ray may hit, in the order they were given so ties
+    // between them come out the same as without the hierarchy
+    pub fn candidates(&self, ray: &Ray) -> Vec<usize> {
+        let mut found = self.unbounded.clone();
+        let mut stack = if self.nodes.is_empty() { vec![] } else { vec![0] };
+        while let Some(n) = stack.pop() {
+            let (bounds, node) = &self.nodes[n];
+            if !bounds.hit(ray) {
+                continue;
+            }
+            match node {
+                Node::Leaf(items) => found.extend(items.iter().filter(|&&i| self.boxes[i].hit(ray))),
+                Node::Inner(left, right) => stack.extend([*left, *right].iter())
+            }
+        }
+        found.sort_unstable();
+        found
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuples::*;
+
+    fn unit_at(x: f64) -> Bounds {
+        Bounds::new(Point3::new(x - 0.5, -0.5, -0.5), Point3::new(x + 0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn visits_only_boxes_along_the_ray() {
+        let boxes: Vec<Bounds> = (0 .. 50).map(|i| unit_at(2.0 * i as f64)).collect();
+        let bvh = Bvh::new(boxes);
+        let r = Ray::new(Point3::new(20.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(bvh.candidates(&r), vec![10]);
+        let along = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(bvh.candidates(&along), (0 .. 50).collect::<Vec<usize>>());
+        let above = Ray::new(Point3::new(-5.0, 2.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        assert!(bvh.candidates(&above).is_empty());
+    }
+
+    #[test]
+    fn unbounded_items_are_always_visited() {
+        let bvh = Bvh::new(vec![unit_at(0.0), Bounds::infinite(), Bounds::empty(), unit_at(4.0)]);
+        let r = Ray::new(Point3::new(4.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(bvh.candidates(&r), vec![1, 3]);
+        assert!(Bvh::new(vec![]).candidates(&r).is_empty());
+    }
+
+}
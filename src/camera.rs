@@ -0,0 +1,315 @@
+use std::f64::consts::PI;
+use crate::tuples::*;
+use crate::geometry::*;
+use crate::transformations::*;
+use crate::random::Rng;
+
+#[derive(Clone, Debug)]
+pub enum Projection {
+    // field of view in degrees
+    Perspective { fov: f64 },
+    // width of the view volume in world units
+    Orthographic { width: f64 },
+    // equidistant fisheye, field of view in degrees across the image circle
+    Fisheye { fov: f64 },
+    // full 360 x 180 degree panorama
+    Equirectangular
+}
+
+#[derive(Clone, Debug)]
+pub enum Convergence {
+    // eyes look straight ahead, infinite zero parallax distance
+    Parallel,
+    // eyes are rotated inwards to meet at the given distance
+    ToeIn { distance: f64 },
+    // parallel eyes with sheared frustums meeting at the given distance
+    OffAxis { distance: f64 }
+}
+
+#[derive(Clone, Debug)]
+pub enum StereoLayout {
+    SideBySide,
+    TopBottom
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Eye {
+    Left,
+    Right
+}
+
+#[derive(Clone, Debug)]
+pub struct Stereo {
+    pub interocular: f64,
+    pub convergence: Convergence,
+    pub layout: StereoLayout
+}
+
+impl Stereo {
+    pub fn new(interocular: f64, convergence: Convergence, layout: StereoLayout) -> Stereo {
+        Stereo {interocular, convergence, layout}
+    }
+}
+
+#[derive(Clone)]
+pub struct Camera {
+    pub hsize: f64,
+    pub vsize: f64,
+    half_width: f64,
+    half_height: f64,
+    pub pxl_sze: f64,
+    pub projection: Projection,
+    pub samples: usize,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    lens_shift: f64,
+    ods_offset: f64,
+    cam_transform: Transformation,
+    inv_transform: Transformation
+}
+
+impl Camera {
+    pub fn default(hsize: f64, vsize: f64, fov: f64) -> Camera {
+        let cam_transform = Transformation::view(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vector3::new(0.0, 1.0, 0.0));
+        Camera::new(hsize, vsize, fov, cam_transform)
+    }
+    pub fn new(hsize: f64, vsize: f64, fov: f64, cam_transform: Transformation) -> Camera {
+        Camera::with_projection(hsize, vsize, Projection::Perspective { fov }, cam_transform)
+    }
+    pub fn orthographic(hsize: f64, vsize: f64, width: f64, cam_transform: Transformation) -> Camera {
+        Camera::with_projection(hsize, vsize, Projection::Orthographic { width }, cam_transform)
+    }
+    pub fn fisheye(hsize: f64, vsize: f64, fov: f64, cam_transform: Transformation) -> Camera {
+        Camera::with_projection(hsize, vsize, Projection::Fisheye { fov }, cam_transform)
+    }
+    pub fn equirectangular(hsize: f64, vsize: f64, cam_transform: Transformation) -> Camera {
+        Camera::with_projection(hsize, vsize, Projection::Equirectangular, cam_transform)
+    }
+    pub fn with_projection(hsize: f64, vsize: f64, projection: Projection, cam_transform: Transformation) -> Camera {
+        let half_view = match projection {
+            Projection::Perspective { fov } => f64::tan(fov.to_radians() / 2.0),
+            Projection::Orthographic { width } => width / 2.0,
+            Projection::Fisheye { .. } => 1.0,
+            Projection::Equirectangular => 1.0
+        };
+        let aspect = hsize / vsize;
+        let half_width = if aspect >= 1.0 {
+            half_view
+        } else {
+            half_view * aspect
+        };
+        let half_height = if aspect >= 1.0 {
+            half_view / aspect
+        } else {
+            half_view
+        };
+        let pxl_sze = (half_width * 2.0) / hsize;
+        Camera {hsize, vsize, half_width, half_height, pxl_sze, projection, samples: 1, shutter_open: 0.0, shutter_close: 0.0, lens_shift: 0.0, ods_offset: 0.0, inv_transform: cam_transform.inverse().unwrap(), cam_transform}
+    }
+    pub fn eye(&self, eye: Eye, stereo: &Stereo) -> Camera {
+        // camera space x points to the viewer's left
+        let offset = match eye {
+            Eye::Left  =>  stereo.interocular / 2.0,
+            Eye::Right => -stereo.interocular / 2.0
+        };
+        let mut cam = Camera {
+            projection: self.projection.clone(),
+            cam_transform: self.cam_transform.clone(),
+            inv_transform: self.inv_transform.clone(),
+            ..*self
+        };
+        if let Projection::Equirectangular = self.projection {
+            cam.ods_offset = offset;
+            return cam;
+        }
+        let to_eye = Transformation::translation(Vector3::new(-offset, 0.0, 0.0));
+        cam.cam_transform = match stereo.convergence {
+            Convergence::ToeIn { distance } => Transformation::chain(&[
+                Transformation::rotate_y(-f64::atan2(offset, distance).to_degrees()),
+                to_eye,
+                self.cam_transform.clone()
+            ]),
+            _ => Transformation::chain(&[to_eye, self.cam_transform.clone()])
+        };
+        cam.inv_transform = cam.cam_transform.inverse().unwrap();
+        if let (Convergence::OffAxis { distance }, Projection::Perspective { .. }) = (&stereo.convergence, &self.projection) {
+            cam.lens_shift = -offset / distance;
+        }
+        cam
+    }
+    pub fn ray4pxl(&self, x: f64, y: f64) -> Option<Ray> {
+        self.ray4pos(x + 0.5, y + 0.5, self.shutter_open)
+    }
+    pub fn ray4sample(&self, x: f64, y: f64, rng: &mut Rng) -> Option<Ray> {
+        let px = x + rng.next_f64();
+        let py = y + rng.next_f64();
+        let time = self.shutter_open + rng.next_f64() * (self.shutter_close - self.shutter_open);
+        self.ray4pos(px, py, time)
+    }
+    fn ray4pos(&self, px: f64, py: f64, time: f64) -> Option<Ray> {
+        let x_offset = px * self.pxl_sze;
+        let y_offset = py * self.pxl_sze;
+        let world_x  = self.half_width - x_offset + self.lens_shift;
+        let world_y  = self.half_height - y_offset;
+        let (origin, direction) = match self.projection {
+            Projection::Perspective { .. } => (
+                Point3::new(0.0, 0.0, 0.0),
+                Vector3::new(world_x, world_y, -1.0)
+            ),
+            Projection::Orthographic { .. } => (
+                Point3::new(world_x, world_y, 0.0),
+                Vector3::new(0.0, 0.0, -1.0)
+            ),
+            Projection::Fisheye { fov } => {
+                let r = f64::sqrt(world_x * world_x + world_y * world_y);
+                if r > 1.0 {
+                    return None;
+                }
+                let theta = r * fov.to_radians() / 2.0;
+                let (dx, dy) = if r == 0.0 { (0.0, 0.0) } else { (world_x / r, world_y / r) };
+                (
+                    Point3::new(0.0, 0.0, 0.0),
+                    Vector3::new(f64::sin(theta) * dx, f64::sin(theta) * dy, -f64::cos(theta))
+                )
+            },
+            Projection::Equirectangular => {
+                let lon = (0.5 - px / self.hsize) * 2.0 * PI;
+                let lat = (0.5 - py / self.vsize) * PI;
+                (
+                    Point3::new(self.ods_offset * f64::cos(lon), 0.0, self.ods_offset * f64::sin(lon)),
+                    Vector3::new(
+                        f64::cos(lat) * f64::sin(lon),
+                        f64::sin(lat),
+                        -f64::cos(lat) * f64::cos(lon)
+                    )
+                )
+            }
+        };
+        let origin = self.inv_transform.transform(&origin);
+        let direction = self.inv_transform.transform(&direction).normalized();
+        Some(Ray::new_at(origin, direction, time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> Transformation {
+        Transformation::view(Point3::origin(), Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 1.0, 0.0))
+    }
+
+    fn assert_dir(ray: &Ray, x: f64, y: f64, z: f64) {
+        let d = ray.dir;
+        assert!((d.x - x).abs() < 1e-9 && (d.y - y).abs() < 1e-9 && (d.z - z).abs() < 1e-9, "{:?}", d);
+    }
+
+    fn assert_point(p: &Point3, x: f64, y: f64, z: f64) {
+        assert!((p.x - x).abs() < 1e-9 && (p.y - y).abs() < 1e-9 && (p.z - z).abs() < 1e-9, "{:?}", p);
+    }
+
+    // where the ray gets to at the given depth in front of the camera
+    fn at_depth(ray: &Ray, depth: f64) -> Point3 {
+        ray.position((-depth - ray.origin.z) / ray.dir.z)
+    }
+
+    #[test]
+    fn parallel_eyes_sit_apart_looking_ahead() {
+        let cam = Camera::new(101.0, 101.0, 60.0, identity());
+        let stereo = Stereo::new(0.06, Convergence::Parallel, StereoLayout::SideBySide);
+        // camera x points to the viewer's left, so the left eye is at +x
+        let left = cam.eye(Eye::Left, &stereo).ray4pos(50.5, 50.5, 0.0).unwrap();
+        assert_point(&left.origin, 0.03, 0.0, 0.0);
+        assert_dir(&left, 0.0, 0.0, -1.0);
+        let right = cam.eye(Eye::Right, &stereo).ray4pos(50.5, 50.5, 0.0).unwrap();
+        assert_point(&right.origin, -0.03, 0.0, 0.0);
+        assert_dir(&right, 0.0, 0.0, -1.0);
+    }
+
+    #[test]
+    fn toed_in_eyes_look_at_the_convergence_point() {
+        let cam = Camera::new(101.0, 101.0, 60.0, identity());
+        let stereo = Stereo::new(0.06, Convergence::ToeIn { distance: 2.0 }, StereoLayout::SideBySide);
+        for (eye, x) in [(Eye::Left, 0.03), (Eye::Right, -0.03)].iter() {
+            let center = cam.eye(*eye, &stereo).ray4pos(50.5, 50.5, 0.0).unwrap();
+            assert_point(&center.origin, *x, 0.0, 0.0);
+            assert!(center.dir.x * x < 0.0);
+            assert_point(&at_depth(&center, 2.0), 0.0, 0.0, -2.0);
+        }
+    }
+
+    #[test]
+    fn off_axis_eyes_meet_at_the_distance() {
+        let cam = Camera::new(101.0, 101.0, 60.0, identity());
+        let stereo = Stereo::new(0.06, Convergence::OffAxis { distance: 3.0 }, StereoLayout::TopBottom);
+        let left = cam.eye(Eye::Left, &stereo);
+        let right = cam.eye(Eye::Right, &stereo);
+        for (cam, x) in [(&left, 0.03), (&right, -0.03)].iter() {
+            let center = cam.ray4pos(50.5, 50.5, 0.0).unwrap();
+            assert_point(&center.origin, *x, 0.0, 0.0);
+            assert_point(&at_depth(&center, 3.0), 0.0, 0.0, -3.0);
+        }
+        // the frustums are sheared, not turned, so rows stay level and the
+        // corners differ by the two lens shifts of 0.01
+        let (l, r) = (left.ray4pos(0.0, 0.0, 0.0).unwrap(), right.ray4pos(0.0, 0.0, 0.0).unwrap());
+        assert!((l.dir.y / l.dir.z - r.dir.y / r.dir.z).abs() < 1e-9);
+        assert!((l.dir.x / l.dir.z - r.dir.x / r.dir.z - 0.02).abs() < 1e-9);
+    }
+
+    #[test]
+    fn panorama_eyes_circle_the_center() {
+        let cam = Camera::equirectangular(360.0, 180.0, identity());
+        let stereo = Stereo::new(0.06, Convergence::Parallel, StereoLayout::TopBottom);
+        let left = cam.eye(Eye::Left, &stereo);
+        let right = cam.eye(Eye::Right, &stereo);
+        // looking ahead the left eye is at +x, looking to the left (+x) it
+        // is behind the center
+        assert_point(&left.ray4pos(180.0, 90.0, 0.0).unwrap().origin, 0.03, 0.0, 0.0);
+        assert_point(&right.ray4pos(180.0, 90.0, 0.0).unwrap().origin, -0.03, 0.0, 0.0);
+        let aside = left.ray4pos(90.0, 90.0, 0.0).unwrap();
+        assert_dir(&aside, 1.0, 0.0, 0.0);
+        assert_point(&aside.origin, 0.0, 0.0, 0.03);
+        // the eyes are always square to the line of sight
+        for px in [10.0, 75.0, 200.0, 333.0].iter() {
+            let r = left.ray4pos(*px, 60.0, 0.0).unwrap();
+            assert!((r.origin - Point3::origin()).dot(&r.dir).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let cam = Camera::orthographic(200.0, 100.0, 4.0, identity());
+        let center = cam.ray4pos(100.0, 50.0, 0.0).unwrap();
+        assert_dir(&center, 0.0, 0.0, -1.0);
+        assert_eq!((center.origin.x, center.origin.y), (0.0, 0.0));
+        // camera x points to the left
+        let corner = cam.ray4pos(0.0, 0.0, 0.0).unwrap();
+        assert_dir(&corner, 0.0, 0.0, -1.0);
+        assert_eq!((corner.origin.x, corner.origin.y), (2.0, 1.0));
+    }
+
+    #[test]
+    fn fisheye_edge_looks_sideways() {
+        let cam = Camera::fisheye(101.0, 101.0, 180.0, identity());
+        assert_dir(&cam.ray4pos(50.5, 50.5, 0.0).unwrap(), 0.0, 0.0, -1.0);
+        assert_dir(&cam.ray4pos(0.0, 50.5, 0.0).unwrap(), 1.0, 0.0, 0.0);
+        assert_dir(&cam.ray4pos(50.5, 0.0, 0.0).unwrap(), 0.0, 1.0, 0.0);
+        // outside the image circle
+        assert!(cam.ray4pos(0.0, 0.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn equirectangular_wraps_around() {
+        let cam = Camera::equirectangular(360.0, 180.0, identity());
+        assert_dir(&cam.ray4pos(180.0, 90.0, 0.0).unwrap(), 0.0, 0.0, -1.0);
+        assert_dir(&cam.ray4pos(90.0, 90.0, 0.0).unwrap(), 1.0, 0.0, 0.0);
+        assert_dir(&cam.ray4pos(180.0, 0.0, 0.0).unwrap(), 0.0, 1.0, 0.0);
+        // both edges look straight back
+        assert_dir(&cam.ray4pos(0.0, 90.0, 0.0).unwrap(), 0.0, 0.0, 1.0);
+        assert_dir(&cam.ray4pos(360.0, 90.0, 0.0).unwrap(), 0.0, 0.0, 1.0);
+    }
+
+}
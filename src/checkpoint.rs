@@ -0,0 +1,143 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use crate::image::Color;
+use crate::progressive::Accumulation;
+use crate::random::Rng;
+
+// little endian dump of an accumulation, the sums are stored as they are so
+// a resumed render continues bit for bit where the interrupted one stopped
+const MAGIC: &[u8; 8] = b"RTCKPT02";
+
+// longer scene names than this mean the file is corrupt
+const MAX_NAME: u64 = 256;
+
+// what the accumulated samples depend on besides the resolution, a checkpoint
+// only resumes the render it was taken from
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Fingerprint {
+    pub scene: String,
+    pub frame: usize,
+    pub reflection_steps: usize,
+    pub seed: u64
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut b = [0; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+fn read_f64<R: Read>(r: &mut R) -> io::Result<f64> {
+    let mut b = [0; 8];
+    r.read_exact(&mut b)?;
+    Ok(f64::from_le_bytes(b))
+}
+
+// elapsed is the render time so far, so time budgets carry over
+pub fn write_checkpoint(acc: &Accumulation, fingerprint: &Fingerprint, elapsed: f64, file: &str) -> io::Result<()> {
+    // a crash while writing must not destroy the previous checkpoint
+    let tmp = format!("{}.tmp", file);
+    {
+        let mut w = BufWriter::new(File::create(&tmp)?);
+        w.write_all(MAGIC)?;
+        w.write_all(&(fingerprint.scene.len() as u64).to_le_bytes())?;
+        w.write_all(fingerprint.scene.as_bytes())?;
+        for v in [fingerprint.frame as u64, fingerprint.reflection_steps as u64, fingerprint.seed].iter() {
+            w.write_all(&v.to_le_bytes())?;
+        }
+        for v in [acc.w as u64, acc.h as u64, acc.passes as u64, acc.rng.state].iter() {
+            w.write_all(&v.to_le_bytes())?;
+        }
+        w.write_all(&elapsed.to_le_bytes())?;
+        for (c, sq) in acc.sum.iter().zip(acc.sum_sq.iter()) {
+            for v in [c.r, c.g, c.b, *sq].iter() {
+                w.write_all(&v.to_le_bytes())?;
+            }
+        }
+        w.flush()?;
+    }
+    fs::rename(&tmp, file)
+}
+
+// everything is checked against the render about to continue before the
+// pixels are allocated, a corrupt header must not ask for gigabytes
+pub fn read_checkpoint(file: &str, fingerprint: &Fingerprint, w: usize, h: usize) -> io::Result<(Accumulation, f64)> {
+    let mut r = BufReader::new(File::open(file)?);
+    let mut magic = [0; 8];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a render checkpoint"));
+    }
+    let len = read_u64(&mut r)?;
+    if len > MAX_NAME {
+        return Err(invalid("corrupt checkpoint"));
+    }
+    let mut scene = vec![0; len as usize];
+    r.read_exact(&mut scene)?;
+    let found = Fingerprint {
+        scene: String::from_utf8_lossy(&scene).into_owned(),
+        frame: read_u64(&mut r)? as usize,
+        reflection_steps: read_u64(&mut r)? as usize,
+        seed: read_u64(&mut r)?
+    };
+    if found != *fingerprint {
+        return Err(invalid(&format!("checkpoint was taken of another render ({:?})", found)));
+    }
+    if read_u64(&mut r)? != w as u64 || read_u64(&mut r)? != h as u64 {
+        return Err(invalid("checkpoint was taken at another resolution"));
+    }
+    let passes = read_u64(&mut r)? as usize;
+    let state = read_u64(&mut r)?;
+    let elapsed = read_f64(&mut r)?;
+    let mut acc = Accumulation::new(w, h, 0);
+    acc.passes = passes;
+    acc.rng = Rng {state};
+    for i in 0 .. w * h {
+        let (red, green, blue) = (read_f64(&mut r)?, read_f64(&mut r)?, read_f64(&mut r)?);
+        acc.sum[i] = Color::new(red, green, blue);
+        acc.sum_sq[i] = read_f64(&mut r)?;
+    }
+    Ok((acc, elapsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenes;
+
+    #[test]
+    fn resume_matches_uninterrupted() {
+        let tracer = scenes::reflections(12.0, 8.0);
+        let mut full = Accumulation::new(12, 8, 5);
+        for _ in 0 .. 4 {
+            full.add_pass(&tracer, 2);
+        }
+        let mut interrupted = Accumulation::new(12, 8, 5);
+        interrupted.add_pass(&tracer, 2);
+        interrupted.add_pass(&tracer, 2);
+        let file = std::env::temp_dir().join(format!("raytracer_resume_test_{}.ckpt", std::process::id()));
+        let file = file.to_str().unwrap();
+        let fingerprint = Fingerprint {scene: "reflections".to_string(), frame: 0, reflection_steps: 2, seed: 5};
+        write_checkpoint(&interrupted, &fingerprint, 1.5, file).unwrap();
+        let other = Fingerprint {reflection_steps: 3, ..fingerprint.clone()};
+        assert!(read_checkpoint(file, &other, 12, 8).is_err());
+        assert!(read_checkpoint(file, &fingerprint, 24, 8).is_err());
+        let (mut resumed, elapsed) = read_checkpoint(file, &fingerprint, 12, 8).unwrap();
+        fs::remove_file(file).unwrap();
+        assert_eq!(elapsed, 1.5);
+        resumed.add_pass(&tracer, 2);
+        resumed.add_pass(&tracer, 2);
+        assert_eq!(resumed.passes, 4);
+        assert_eq!(resumed.sum_sq, full.sum_sq);
+        for (a, b) in resumed.sum.iter().zip(full.sum.iter()) {
+            assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+        }
+    }
+
+}
@@ -0,0 +1,98 @@
+// transfer functions between linear light and encoded display values,
+// both sRGB and Rec.709 share the same primaries as our working space
+// the exact Rec.709 constants, the rounded 1.099 and 0.018 leave a step
+// where the two pieces meet
+const REC709_ALPHA: f64 = 1.099_296_826_809_442;
+const REC709_BETA: f64 = 0.018_053_968_510_807;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transfer {
+    Linear,
+    Srgb,
+    Rec709
+}
+
+impl Transfer {
+
+    pub fn parse(name: &str) -> Option<Transfer> {
+        match name {
+            "linear" => Some(Transfer::Linear),
+            "srgb" => Some(Transfer::Srgb),
+            "rec709" => Some(Transfer::Rec709),
+            _ => None
+        }
+    }
+
+    // linear [0, 1] to encoded [0, 1]
+    pub fn encode(&self, x: f64) -> f64 {
+        let x = x.clamp(0.0, 1.0);
+        match *self {
+            Transfer::Linear => x,
+            Transfer::Srgb => if x <= 0.003_130_8 {
+                12.92 * x
+            } else {
+                1.055 * f64::powf(x, 1.0 / 2.4) - 0.055
+            },
+            Transfer::Rec709 => if x < REC709_BETA {
+                4.5 * x
+            } else {
+                REC709_ALPHA * f64::powf(x, 0.45) - (REC709_ALPHA - 1.0)
+            }
+        }
+    }
+
+    // encoded [0, 1] back to linear [0, 1]
+    pub fn decode(&self, v: f64) -> f64 {
+        let v = v.clamp(0.0, 1.0);
+        match *self {
+            Transfer::Linear => v,
+            Transfer::Srgb => if v <= 0.040_45 {
+                v / 12.92
+            } else {
+                f64::powf((v + 0.055) / 1.055, 2.4)
+            },
+            Transfer::Rec709 => if v < 4.5 * REC709_BETA {
+                v / 4.5
+            } else {
+                f64::powf((v + REC709_ALPHA - 1.0) / REC709_ALPHA, 1.0 / 0.45)
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [Transfer; 3] = [Transfer::Linear, Transfer::Srgb, Transfer::Rec709];
+
+    #[test]
+    fn encode_and_decode_round_trip() {
+        for t in CURVES.iter() {
+            for i in 0 ..= 1000 {
+                let x = i as f64 / 1000.0;
+                assert!((t.decode(t.encode(x)) - x).abs() < 1e-9, "{:?} {}", t, x);
+                assert!((t.encode(t.decode(x)) - x).abs() < 1e-9, "{:?} {}", t, x);
+            }
+            assert_eq!((t.encode(0.0), t.decode(0.0)), (0.0, 0.0));
+            assert!((t.encode(1.0) - 1.0).abs() < 1e-12 && (t.decode(1.0) - 1.0).abs() < 1e-12);
+            // out of range values are clamped
+            assert_eq!((t.encode(-0.5), t.encode(3.0)), (0.0, t.encode(1.0)));
+        }
+        assert!((Transfer::Srgb.encode(0.5) - 0.735_356_983).abs() < 1e-6);
+        assert!((Transfer::Rec709.encode(0.5) - 0.705_435_553).abs() < 1e-6);
+    }
+
+    #[test]
+    fn pieces_meet_at_the_breakpoints() {
+        for (t, x) in [(Transfer::Srgb, 0.003_130_8), (Transfer::Rec709, REC709_BETA)].iter() {
+            let (below, above) = (t.encode(x - 1e-12), t.encode(x + 1e-12));
+            assert!((above - below).abs() < 1e-7, "{:?} {} {}", t, below, above);
+            let v = t.encode(*x);
+            let (below, above) = (t.decode(v - 1e-12), t.decode(v + 1e-12));
+            assert!((above - below).abs() < 1e-7, "{:?} {} {}", t, below, above);
+        }
+    }
+
+}
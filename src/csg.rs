@@ -0,0 +1,190 @@
+use crate::tuples::*;
+use crate::geometry::*;
+use crate::material::Material;
+use crate::bounds::Bounds;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    // the left solid with the right one cut away
+    Difference
+}
+
+impl CsgOp {
+
+    // whether crossing the surface of one child bounds the combined solid,
+    // given which children the ray is inside of at that point
+    fn allows(&self, left_hit: bool, in_left: bool, in_right: bool) -> bool {
+        match *self {
+            CsgOp::Union => if left_hit { !in_right } else { !in_left },
+            CsgOp::Intersection => if left_hit { in_right } else { in_left },
+            CsgOp::Difference => if left_hit { !in_right } else { in_left }
+        }
+    }
+
+    fn contains(&self, in_left: bool, in_right: bool) -> bool {
+        match *self {
+            CsgOp::Union => in_left || in_right,
+            CsgOp::Intersection => in_left && in_right,
+            CsgOp::Difference => in_left && !in_right
+        }
+    }
+
+}
+
+// two solids combined into one, the children keep their own transformations
+// and materials so a cut shows the material of the shape that cut it
+pub struct Csg {
+    op: CsgOp,
+    left: Box<dyn Primitive>,
+    right: Box<dyn Primitive>
+}
+
+impl Csg {
+
+    pub fn new(op: CsgOp, left: Box<dyn Primitive>, right: Box<dyn Primitive>) -> Csg {
+        Csg{op, left, right}
+    }
+
+    // the child the outermost step of the path leads to and the rest of it
+    fn child<'a>(&'a self, path: &'a [usize]) -> (&'a dyn Primitive, &'a [usize]) {
+        match path.split_last() {
+            Some((1, rest)) => (self.right.as_ref(), rest),
+            Some((_, rest)) => (self.left.as_ref(), rest),
+            None => (self.left.as_ref(), path)
+        }
+    }
+
+}
+
+impl Primitive for Csg {
+
+    // walks the crossings of both children front to back keeping track of
+    // which solids the ray is in, only the ones on the combined surface stay
+    fn intersect(&self, ray: &Ray) -> Vec<Intersection> {
+        let mut crossings: Vec<(bool, Intersection)> = self.left.intersect(ray).into_iter().map(|i| (true, i))
+            .chain(self.right.intersect(ray).into_iter().map(|i| (false, i)))
+            .collect();
+        crossings.sort_by(|a, b| a.1.dist.partial_cmp(&b.1.dist).unwrap());
+        let mut in_left = self.left.starts_inside(ray);
+        let mut in_right = self.right.starts_inside(ray);
+        let mut kept = vec![];
+        for (left_hit, mut i) in crossings {
+            if self.op.allows(left_hit, in_left, in_right) {
+                i.handle.path.push(if left_hit { 0 } else { 1 });
+                kept.push(i);
+            }
+            if left_hit {
+                in_left = !in_left;
+            } else {
+                in_right = !in_right;
+            }
+        }
+        kept
+    }
+
+    fn surface_normal(&self, p: &Point3) -> Normal3 {
+        self.normal_along(p, &[], 0.0)
+    }
+
+    fn normal_along(&self, p: &Point3, path: &[usize], time: f64) -> Normal3 {
+        let (child, rest) = self.child(path);
+        child.normal_along(p, rest, time)
+    }
+
+    fn material(&self) -> Material {
+        self.left.material()
+    }
+
+    fn material_along(&self, path: &[usize]) -> Material {
+        let (child, rest) = self.child(path);
+        child.material_along(rest)
+    }
+
+    fn starts_inside(&self, ray: &Ray) -> bool {
+        self.op.contains(self.left.starts_inside(ray), self.right.starts_inside(ray))
+    }
+
+    fn bounds(&self) -> Bounds {
+        match self.op {
+            CsgOp::Union => self.left.bounds().union(&self.right.bounds()),
+            CsgOp::Intersection => self.left.bounds().intersection(&self.right.bounds()),
+            CsgOp::Difference => self.left.bounds()
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Color;
+    use crate::transformations::*;
+
+    fn sphere(scale: f64, color: Color) -> Box<dyn Primitive> {
+        Box::new(UnitSphere::new(Transformation::scale(Vector3::new(scale, scale, scale)), Material::from_color(color)))
+    }
+
+    fn dists(intersections: &[Intersection]) -> Vec<f64> {
+        intersections.iter().map(|i| i.dist).collect()
+    }
+
+    #[test]
+    fn allowed_crossings() {
+        // left hit, inside left, inside right
+        let cases = [
+            (true, true, true), (true, true, false), (true, false, true), (true, false, false),
+            (false, true, true), (false, true, false), (false, false, true), (false, false, false)
+        ];
+        let expected = [
+            (CsgOp::Union, [false, true, false, true, false, false, true, true]),
+            (CsgOp::Intersection, [true, false, true, false, true, true, false, false]),
+            (CsgOp::Difference, [false, true, false, true, true, true, false, false])
+        ];
+        for (op, allowed) in expected.iter() {
+            for ((l, il, ir), a) in cases.iter().zip(allowed.iter()) {
+                assert_eq!(op.allows(*l, *il, *ir), *a, "{:?} {} {} {}", op, l, il, ir);
+            }
+        }
+    }
+
+    #[test]
+    fn hollow_sphere() {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let shell = Csg::new(CsgOp::Difference, sphere(1.0, white.clone()), sphere(0.5, Color::new(1.0, 0.0, 0.0)));
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        let xs = shell.intersect(&r);
+        assert_eq!(dists(&xs), vec![4.0, 4.5, 5.5, 6.0]);
+        // the inner surface belongs to the cutting sphere and faces inwards
+        let inner = &xs[1];
+        assert_eq!(shell.material_along(&inner.handle.path).color.g, 0.0);
+        let n = shell.normal_along(&r.position(inner.dist), &inner.handle.path, 0.0);
+        assert!((n.z + 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn union_and_intersection() {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        let union = Csg::new(CsgOp::Union, sphere(1.0, white.clone()), sphere(0.5, white.clone()));
+        assert_eq!(dists(&union.intersect(&r)), vec![4.0, 6.0]);
+        let both = Csg::new(CsgOp::Intersection, sphere(1.0, white.clone()), sphere(0.5, white));
+        assert_eq!(dists(&both.intersect(&r)), vec![4.5, 5.5]);
+    }
+
+    #[test]
+    fn half_space_cut() {
+        // the floor plane is solid below, so cutting a sphere with it keeps
+        // the upper half and closes it with a disc
+        let white = Color::new(1.0, 1.0, 1.0);
+        let floor: Box<dyn Primitive> = Box::new(Plane::new(Transformation::identity(), Material::from_color(white.clone())));
+        let dome = Csg::new(CsgOp::Difference, sphere(1.0, white), floor);
+        let down = Ray::new(Point3::new(0.0, 5.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        assert_eq!(dists(&dome.intersect(&down)), vec![4.0, 5.0]);
+        let up = Ray::new(Point3::new(0.0, -5.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        assert!(dome.right.starts_inside(&up));
+        assert_eq!(dists(&dome.intersect(&up)), vec![5.0, 6.0]);
+    }
+
+}
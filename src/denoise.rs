@@ -0,0 +1,196 @@
+use crate::image::*;
+use crate::aov::*;
+
+// the guide buffers both filters need rendered alongside the beauty image
+pub const GUIDES: [Pass; 3] = [Pass::Albedo, Pass::Normal, Pass::Depth];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Denoiser {
+    // edge avoiding a-trous wavelet filter, Dammertz et al. 2010, every
+    // iteration doubles the reach of its 5x5 kernel
+    Atrous { iterations: usize },
+    // joint bilateral filter over a square window
+    Bilateral { radius: usize }
+}
+
+// how quickly the weights fall off with differences in the guides
+const SIGMA_COLOR: f64 = 0.6;
+const SIGMA_NORMAL: f64 = 0.3;
+const SIGMA_DEPTH: f64 = 0.05;
+const SIGMA_ALBEDO: f64 = 0.1;
+
+const B3_SPLINE: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+struct Guides {
+    albedo: Vec<[f64; 3]>,
+    normal: Vec<[f64; 3]>,
+    depth: Vec<f64>,
+    hit: Vec<bool>
+}
+
+impl Guides {
+
+    // missing guides simply do not take part in the weights
+    fn new(passes: &RenderPasses) -> Guides {
+        let n = passes.beauty.w * passes.beauty.h;
+        let rgb = |pass| match passes.pass(pass) {
+            Some(img) => img.pixels().map(|(_, _, c)| [c.r, c.g, c.b]).collect(),
+            None => vec![[0.0; 3]; n]
+        };
+        let depth = match passes.pass(Pass::Depth) {
+            Some(img) => img.pixels().map(|(_, _, c)| c.r).collect(),
+            None => vec![0.0; n]
+        };
+        let hit = match passes.pass(Pass::Normal).or_else(|| passes.pass(Pass::Depth)) {
+            Some(img) => img.pixels().map(|(_, _, c)| c.a > 0.0).collect(),
+            None => vec![true; n]
+        };
+        Guides {albedo: rgb(Pass::Albedo), normal: rgb(Pass::Normal), depth, hit}
+    }
+
+    // edge stopping weight between pixels p and q, step is how many pixels
+    // apart they are, so depth may drift further over larger distances
+    fn weight(&self, p: usize, q: usize, step: f64) -> f64 {
+        if self.hit[p] != self.hit[q] {
+            return 0.0;
+        }
+        let dist2 = |a: &[f64; 3], b: &[f64; 3]| (0 .. 3).map(|i| (a[i] - b[i]).powi(2)).sum::<f64>();
+        let normal = dist2(&self.normal[p], &self.normal[q]) / (SIGMA_NORMAL * SIGMA_NORMAL);
+        let albedo = dist2(&self.albedo[p], &self.albedo[q]) / (SIGMA_ALBEDO * SIGMA_ALBEDO);
+        let dz = (self.depth[p] - self.depth[q]) / (self.depth[p].max(1e-3) * SIGMA_DEPTH * step);
+        f64::exp(-(normal + albedo + dz * dz))
+    }
+
+}
+
+impl Denoiser {
+
+    // atrous, atrous:ITERATIONS, bilateral or bilateral:RADIUS
+    pub fn parse(name: &str) -> Option<Denoiser> {
+        let mut parts = name.splitn(2, ':');
+        match (parts.next()?, parts.next()) {
+            ("atrous", None) => Some(Denoiser::Atrous { iterations: 5 }),
+            ("atrous", Some(n)) => n.parse().ok().map(|iterations| Denoiser::Atrous { iterations }),
+            ("bilateral", None) => Some(Denoiser::Bilateral { radius: 5 }),
+            ("bilateral", Some(r)) => r.parse().ok().map(|radius| Denoiser::Bilateral { radius }),
+            _ => None
+        }
+    }
+
+    // filters the beauty image of the passes guided by albedo, normal and
+    // depth, the lighting is divided by the albedo first so that texture
+    // and color edges survive untouched
+    pub fn apply(&self, passes: &RenderPasses) -> Image {
+        let img = &passes.beauty;
+        let (w, h) = (img.w, img.h);
+        let guides = Guides::new(passes);
+        let demodulate = |c: f64, a: f64| if a > 1e-3 { c / a } else { c };
+        let mut light: Vec<[f64; 3]> = img.pixels()
+            .enumerate()
+            .map(|(i, (_, _, c))| {
+                let a = &guides.albedo[i];
+                [demodulate(c.r, a[0]), demodulate(c.g, a[1]), demodulate(c.b, a[2])]
+            })
+            .collect();
+        match *self {
+            Denoiser::Atrous { iterations } => {
+                for i in 0 .. iterations {
+                    let step = 1 << i;
+                    // the color tolerance shrinks as the kernel grows
+                    let sigma = SIGMA_COLOR / f64::powi(2.0, i as i32);
+                    let mut taps = vec![];
+                    for (ty, ky) in B3_SPLINE.iter().enumerate() {
+                        for (tx, kx) in B3_SPLINE.iter().enumerate() {
+                            taps.push(((tx as i64 - 2) * step, (ty as i64 - 2) * step, kx * ky));
+                        }
+                    }
+                    light = filter(&light, &guides, w, h, sigma, &taps);
+                }
+            },
+            Denoiser::Bilateral { radius } => {
+                let sigma = (radius as f64 / 2.0).max(0.5);
+                let r = radius as i64;
+                let mut taps = vec![];
+                for dy in -r ..= r {
+                    for dx in -r ..= r {
+                        taps.push((dx, dy, f64::exp(-((dx * dx + dy * dy) as f64) / (2.0 * sigma * sigma))));
+                    }
+                }
+                light = filter(&light, &guides, w, h, SIGMA_COLOR, &taps);
+            }
+        }
+        let mut out = img.clone();
+        for (i, (_, _, c)) in out.pixels_mut().enumerate() {
+            let a = &guides.albedo[i];
+            let modulate = |v: f64, a: f64| if a > 1e-3 { v * a } else { v };
+            c.r = modulate(light[i][0], a[0]);
+            c.g = modulate(light[i][1], a[1]);
+            c.b = modulate(light[i][2], a[2]);
+        }
+        out
+    }
+
+}
+
+// one pass of a cross filter over the taps, given as offset and spatial weight
+fn filter(light: &[[f64; 3]], guides: &Guides, w: usize, h: usize, sigma_color: f64, taps: &[(i64, i64, f64)]) -> Vec<[f64; 3]> {
+    let mut out = vec![[0.0; 3]; light.len()];
+    for y in 0 .. h as i64 {
+        for x in 0 .. w as i64 {
+            let p = (y * w as i64 + x) as usize;
+            let mut sum = [0.0; 3];
+            let mut total = 0.0;
+            for (dx, dy, spatial) in taps.iter() {
+                let (qx, qy) = (x + dx, y + dy);
+                if qx < 0 || qy < 0 || qx >= w as i64 || qy >= h as i64 {
+                    continue;
+                }
+                let q = (qy * w as i64 + qx) as usize;
+                let step = (dx.abs().max(dy.abs()) as f64).max(1.0);
+                let color = (0 .. 3).map(|i| (light[p][i] - light[q][i]).powi(2)).sum::<f64>()
+                    / (sigma_color * sigma_color);
+                let weight = spatial * guides.weight(p, q, step) * f64::exp(-color);
+                for i in 0 .. 3 {
+                    sum[i] += weight * light[q][i];
+                }
+                total += weight;
+            }
+            // the center tap always carries weight, so total never vanishes
+            out[p] = [sum[0] / total, sum[1] / total, sum[2] / total];
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenes;
+    use crate::random::Rng;
+    use crate::diff::compare;
+
+    #[test]
+    fn removes_noise() {
+        let tracer = scenes::room(96.0, 48.0).frame(0.0);
+        let clean = tracer.trace_passes(5, &GUIDES);
+        let mut noisy = tracer.trace_passes(5, &GUIDES);
+        let mut rng = Rng::new(7);
+        for (_, _, c) in noisy.beauty.pixels_mut() {
+            let k = 1.0 + (rng.next_f64() - 0.5) * 0.4;
+            *c = c.scale(k);
+        }
+        let before = compare(&noisy.beauty, &clean.beauty).unwrap().psnr;
+        for denoiser in [Denoiser::Atrous { iterations: 5 }, Denoiser::Bilateral { radius: 5 }].iter() {
+            let after = compare(&denoiser.apply(&noisy), &clean.beauty).unwrap().psnr;
+            assert!(after > before + 3.0, "{:?}: {} dB before, {} dB after", denoiser, before, after);
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Denoiser::parse("atrous"), Some(Denoiser::Atrous { iterations: 5 }));
+        assert_eq!(Denoiser::parse("bilateral:3"), Some(Denoiser::Bilateral { radius: 3 }));
+        assert_eq!(Denoiser::parse("median"), None);
+    }
+
+}
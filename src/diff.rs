@@ -0,0 +1,278 @@
+use crate::image::*;
+use crate::colorspace::Transfer;
+
+// all metrics work on what ends up on screen, i.e. after each image's tone
+// mapping, so a linear render and its png agree
+pub struct Diff {
+    pub mse: f64,
+    pub psnr: f64,
+    pub ssim: f64,
+    pub flip: f64,
+    // per pixel flip error in [0, 1], row by row
+    pub error: Vec<f64>,
+    pub w: usize,
+    pub h: usize
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    Mse,
+    Psnr,
+    Ssim,
+    Flip
+}
+
+impl Metric {
+
+    pub fn parse(name: &str) -> Option<Metric> {
+        match name {
+            "mse" => Some(Metric::Mse),
+            "psnr" => Some(Metric::Psnr),
+            "ssim" => Some(Metric::Ssim),
+            "flip" => Some(Metric::Flip),
+            _ => None
+        }
+    }
+
+    pub fn value(&self, diff: &Diff) -> f64 {
+        match *self {
+            Metric::Mse => diff.mse,
+            Metric::Psnr => diff.psnr,
+            Metric::Ssim => diff.ssim,
+            Metric::Flip => diff.flip
+        }
+    }
+
+    // psnr and ssim grow with similarity, mse and flip with the error
+    pub fn exceeds(&self, diff: &Diff, threshold: f64) -> bool {
+        let v = self.value(diff);
+        match *self {
+            Metric::Mse | Metric::Flip => v > threshold,
+            Metric::Psnr | Metric::Ssim => v < threshold
+        }
+    }
+
+}
+
+fn display(img: &Image) -> Vec<[f64; 3]> {
+    img.pixels()
+        .map(|(_, _, c)| {
+            let c = img.tone_mapping.apply(c);
+            [c.r, c.g, c.b]
+        })
+        .collect()
+}
+
+fn luma(c: &[f64; 3]) -> f64 {
+    0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]
+}
+
+fn gaussian(sigma: f64) -> Vec<f64> {
+    let radius = (3.0 * sigma).ceil() as i64;
+    let kernel: Vec<f64> = (-radius ..= radius)
+        .map(|i| f64::exp(-((i * i) as f64) / (2.0 * sigma * sigma)))
+        .collect();
+    let sum: f64 = kernel.iter().sum();
+    kernel.iter().map(|k| k / sum).collect()
+}
+
+// separable convolution clamping at the borders
+fn blur(v: &[f64], w: usize, h: usize, kernel: &[f64]) -> Vec<f64> {
+    let r = (kernel.len() / 2) as i64;
+    let clamp = |i: i64, n: usize| i.max(0).min(n as i64 - 1) as usize;
+    let mut tmp = vec![0.0; v.len()];
+    for y in 0 .. h {
+        for x in 0 .. w {
+            tmp[y * w + x] = kernel.iter().enumerate()
+                .map(|(k, f)| f * v[y * w + clamp(x as i64 + k as i64 - r, w)])
+                .sum();
+        }
+    }
+    let mut out = vec![0.0; v.len()];
+    for y in 0 .. h {
+        for x in 0 .. w {
+            out[y * w + x] = kernel.iter().enumerate()
+                .map(|(k, f)| f * tmp[clamp(y as i64 + k as i64 - r, h) * w + x])
+                .sum();
+        }
+    }
+    out
+}
+
+// mean structural similarity of the luma with the usual 11 tap gaussian window
+fn ssim(a: &[f64], b: &[f64], w: usize, h: usize) -> f64 {
+    let (c1, c2) = (0.01 * 0.01, 0.03 * 0.03);
+    let kernel = gaussian(1.5);
+    let product = |p: &[f64], q: &[f64]| p.iter().zip(q.iter()).map(|(x, y)| x * y).collect::<Vec<f64>>();
+    let mu_a = blur(a, w, h, &kernel);
+    let mu_b = blur(b, w, h, &kernel);
+    let aa = blur(&product(a, a), w, h, &kernel);
+    let bb = blur(&product(b, b), w, h, &kernel);
+    let ab = blur(&product(a, b), w, h, &kernel);
+    let sum: f64 = (0 .. w * h)
+        .map(|i| {
+            let (ma, mb) = (mu_a[i], mu_b[i]);
+            let var_a = aa[i] - ma * ma;
+            let var_b = bb[i] - mb * mb;
+            let cov = ab[i] - ma * mb;
+            ((2.0 * ma * mb + c1) * (2.0 * cov + c2)) / ((ma * ma + mb * mb + c1) * (var_a + var_b + c2))
+        })
+        .sum();
+    sum / (w * h) as f64
+}
+
+fn lab(c: &[f64; 3]) -> [f64; 3] {
+    let lin: Vec<f64> = c.iter().map(|v| Transfer::Srgb.decode(*v)).collect();
+    let x = (0.4124 * lin[0] + 0.3576 * lin[1] + 0.1805 * lin[2]) / 0.9505;
+    let y = 0.2126 * lin[0] + 0.7152 * lin[1] + 0.0722 * lin[2];
+    let z = (0.0193 * lin[0] + 0.1192 * lin[1] + 0.9505 * lin[2]) / 1.089;
+    let f = |t: f64| if t > 0.008_856 { t.cbrt() } else { 7.787 * t + 16.0 / 116.0 };
+    [116.0 * f(y) - 16.0, 500.0 * (f(x) - f(y)), 200.0 * (f(y) - f(z))]
+}
+
+// |dL| + |dab|, a better fit than plain euclidean for large differences
+fn hyab(p: &[f64; 3], q: &[f64; 3]) -> f64 {
+    (p[0] - q[0]).abs() + f64::sqrt((p[1] - q[1]).powi(2) + (p[2] - q[2]).powi(2))
+}
+
+// gradient magnitude of the lightness scaled to [0, 1]
+fn edges(l: &[f64], w: usize, h: usize) -> Vec<f64> {
+    let at = |x: i64, y: i64| l[y.max(0).min(h as i64 - 1) as usize * w + x.max(0).min(w as i64 - 1) as usize];
+    let mut g = vec![0.0; l.len()];
+    for y in 0 .. h as i64 {
+        for x in 0 .. w as i64 {
+            let dx = (at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1))
+                - (at(x - 1, y - 1) + 2.0 * at(x - 1, y) + at(x - 1, y + 1));
+            let dy = (at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1))
+                - (at(x - 1, y - 1) + 2.0 * at(x, y - 1) + at(x + 1, y - 1));
+            g[y as usize * w + x as usize] = (f64::sqrt(dx * dx + dy * dy) / (4.0 * 100.0)).min(1.0);
+        }
+    }
+    g
+}
+
+// a simplified take on NVIDIA's FLIP: both images are low passed the way the
+// eye would at normal viewing distance, compared by HyAB distance in Lab and
+// the color error is then amplified where edges differ
+fn flip(a: &[[f64; 3]], b: &[[f64; 3]], w: usize, h: usize) -> Vec<f64> {
+    let kernel = gaussian(1.0);
+    let filtered = |img: &[[f64; 3]]| -> Vec<[f64; 3]> {
+        let channels: Vec<Vec<f64>> = (0 .. 3)
+            .map(|c| blur(&img.iter().map(|p| p[c]).collect::<Vec<f64>>(), w, h, &kernel))
+            .collect();
+        (0 .. w * h).map(|i| lab(&[channels[0][i], channels[1][i], channels[2][i]])).collect()
+    };
+    let (la, lb) = (filtered(a), filtered(b));
+    // the largest distance inside the gamut, between pure green and pure blue
+    let max = hyab(&lab(&[0.0, 1.0, 0.0]), &lab(&[0.0, 0.0, 1.0])).powf(0.7);
+    let lightness = |img: &[[f64; 3]]| img.iter().map(|p| lab(p)[0]).collect::<Vec<f64>>();
+    let (ea, eb) = (edges(&lightness(a), w, h), edges(&lightness(b), w, h));
+    (0 .. w * h)
+        .map(|i| {
+            let color = (hyab(&la[i], &lb[i]).powf(0.7) / max).min(1.0);
+            let feature = (ea[i] - eb[i]).abs().sqrt();
+            color.powf(1.0 - feature)
+        })
+        .collect()
+}
+
+pub fn compare(a: &Image, b: &Image) -> Result<Diff, String> {
+    if a.w != b.w || a.h != b.h {
+        return Err(format!("size mismatch: {}x{} against {}x{}", a.w, a.h, b.w, b.h));
+    }
+    // every metric is a mean over the pixels
+    if a.w == 0 || a.h == 0 {
+        return Err("cannot compare empty images".to_string());
+    }
+    let (w, h) = (a.w, a.h);
+    let (da, db) = (display(a), display(b));
+    let mse = da.iter().zip(db.iter())
+        .map(|(p, q)| (0 .. 3).map(|c| (p[c] - q[c]).powi(2)).sum::<f64>())
+        .sum::<f64>() / (w * h * 3) as f64;
+    let psnr = if mse > 0.0 { 10.0 * f64::log10(1.0 / mse) } else { f64::INFINITY };
+    let ssim = ssim(
+        &da.iter().map(luma).collect::<Vec<f64>>(),
+        &db.iter().map(luma).collect::<Vec<f64>>(),
+        w, h);
+    let error = flip(&da, &db, w, h);
+    let flip = error.iter().sum::<f64>() / (w * h) as f64;
+    Ok(Diff {mse, psnr, ssim, flip, error, w, h})
+}
+
+// stops of the magma color map, from no error in black to full error in white
+const HEAT: [(f64, f64, f64); 5] = [
+    (0.0, 0.0, 0.016),
+    (0.317, 0.071, 0.486),
+    (0.716, 0.215, 0.475),
+    (0.987, 0.536, 0.382),
+    (0.987, 0.991, 0.749)
+];
+
+impl Diff {
+
+    pub fn heatmap(&self) -> Image {
+        let mut img = Image::new_rgba(self.w, self.h);
+        for (i, e) in self.error.iter().enumerate() {
+            let t = e.clamp(0.0, 1.0) * (HEAT.len() - 1) as f64;
+            let k = usize::min(t.floor() as usize, HEAT.len() - 2);
+            let f = t - k as f64;
+            let (p, q) = (HEAT[k], HEAT[k + 1]);
+            let c = Color::from_srgb(
+                p.0 + (q.0 - p.0) * f,
+                p.1 + (q.1 - p.1) * f,
+                p.2 + (q.2 - p.2) * f);
+            img.set_rgb(i % self.w, i / self.w, &c);
+        }
+        img
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tonemap::{ToneMap, ToneMapping};
+
+    // displayed as they are, no gamma
+    fn gray(w: usize, h: usize, v: f64) -> Image {
+        let mut img = Image::new_rgba(w, h);
+        img.tone_mapping = ToneMapping::new(0.0, ToneMap::Clip, Transfer::Linear);
+        for (_, _, c) in img.pixels_mut() {
+            *c = Color::new(v, v, v);
+        }
+        img
+    }
+
+    #[test]
+    fn identical_images() {
+        let mut img = gray(24, 16, 0.3);
+        img.set_rgb(5, 5, &Color::new(1.0, 0.0, 0.0));
+        let d = compare(&img, &img.clone()).unwrap();
+        assert_eq!((d.mse, d.psnr, d.flip), (0.0, f64::INFINITY, 0.0));
+        assert!((d.ssim - 1.0).abs() < 1e-9);
+        // no error is the dark end of the color map everywhere
+        let heat = d.heatmap();
+        let dark = Color::from_srgb(HEAT[0].0, HEAT[0].1, HEAT[0].2);
+        assert!(heat.pixels().all(|(_, _, c)| (c.r, c.g, c.b) == (dark.r, dark.g, dark.b)));
+    }
+
+    #[test]
+    fn constant_offset() {
+        let d = compare(&gray(24, 16, 0.5), &gray(24, 16, 0.6)).unwrap();
+        assert!((d.mse - 0.01).abs() < 1e-12);
+        assert!((d.psnr - 20.0).abs() < 1e-9);
+        assert!(d.ssim < 1.0 && d.flip > 0.0);
+        // further apart is worse by every metric
+        let far = compare(&gray(24, 16, 0.0), &gray(24, 16, 1.0)).unwrap();
+        assert_eq!((far.mse, far.psnr), (1.0, 0.0));
+        assert!(far.ssim < d.ssim && far.flip > d.flip);
+        assert!(Metric::Psnr.exceeds(&far, 30.0) && !Metric::Mse.exceeds(&d, 0.02));
+    }
+
+    #[test]
+    fn rejects_empty_and_mismatched_images() {
+        assert!(compare(&gray(0, 0, 0.0), &gray(0, 0, 0.0)).is_err());
+        assert!(compare(&gray(4, 4, 0.0), &gray(4, 5, 0.0)).is_err());
+    }
+
+}
@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{mpsc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::image::*;
+use crate::aov::RenderPasses;
+use crate::raytracer::*;
+use crate::scenes;
+use crate::stats::*;
+
+// the coordinator connects to every worker, names the scene once and then
+// asks for one tile after the other, a line of text each:
+//
+//   JOB scene w h samples reflection_steps   answered with OK or ERR message
+//   TILE frame x y w h                       answered with w * h pixels
+//
+// pixels come back as little endian f64 r, g, b, a followed by the ray
+// counts the tile took, workers build the scene from scenes::frame_by_name
+// so every box renders exactly the same pixels
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// a rectangle of one frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tile {
+    pub frame: usize,
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize
+}
+
+// what all tiles of a render share
+#[derive(Clone, Debug, PartialEq)]
+pub struct Job {
+    pub scene: String,
+    pub w: usize,
+    pub h: usize,
+    // the camera's own when not given
+    pub samples: Option<usize>,
+    pub reflection_steps: usize
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut b = [0; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+fn read_f64<R: Read>(r: &mut R) -> io::Result<f64> {
+    let mut b = [0; 8];
+    r.read_exact(&mut b)?;
+    Ok(f64::from_le_bytes(b))
+}
+
+impl Tile {
+
+    fn parse(line: &str) -> Option<Tile> {
+        let mut words = line.split_whitespace();
+        if words.next()? != "TILE" {
+            return None;
+        }
+        let mut n = || words.next()?.parse::<usize>().ok();
+        Some(Tile {frame: n()?, x: n()?, y: n()?, w: n()?, h: n()?})
+    }
+
+}
+
+impl Job {
+
+    fn parse(line: &str) -> Option<Job> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["JOB", scene, w, h, samples, steps] => Some(Job {
+                scene: scene.to_string(),
+                w: w.parse().ok()?,
+                h: h.parse().ok()?,
+                samples: if *samples == "-" { None } else { Some(samples.parse().ok()?) },
+                reflection_steps: steps.parse().ok()?
+            }),
+            _ => None
+        }
+    }
+
+    fn header(&self) -> String {
+        let samples = self.samples.map_or("-".to_string(), |s| s.to_string());
+        format!("JOB {} {} {} {} {}", self.scene, self.w, self.h, samples, self.reflection_steps)
+    }
+
+    fn tracer(&self, frame: usize) -> Option<RayTracer> {
+        scenes::frame_by_name(&self.scene, self.w as f64, self.h as f64, frame as f64)
+    }
+
+}
+
+// answers coordinators until the process is killed, every connection is
+// served on a thread of its own
+pub fn serve(listener: TcpListener) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        thread::spawn(move || {
+            let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+            if let Err(e) = serve_connection(stream) {
+                eprintln!("worker: {}: {}", peer, e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn serve_connection(stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let job = match Job::parse(&line) {
+        Some(job) if job.tracer(0).is_some() => job,
+        _ => {
+            writeln!(writer, "ERR cannot render {}", line.trim())?;
+            return writer.flush();
+        }
+    };
+    writeln!(writer, "OK")?;
+    writer.flush()?;
+    let settings = RenderSettings {samples: job.samples, reflection_steps: job.reflection_steps, ..RenderSettings::default()};
+    // tiles usually arrive frame by frame, so only the last scene is kept
+    let mut current: Option<(usize, RayTracer)> = None;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let tile = Tile::parse(&line).ok_or_else(|| invalid("expected a tile"))?;
+        if tile.x + tile.w > job.w || tile.y + tile.h > job.h {
+            return Err(invalid("tile outside of the image"));
+        }
+        if current.as_ref().map(|(frame, _)| *frame) != Some(tile.frame) {
+            current = Some((tile.frame, job.tracer(tile.frame).unwrap()));
+        }
+        let tracer = &current.as_ref().unwrap().1;
+        let before = tracer.stats();
+        let img = tracer.render_tile(&settings, tile.x, tile.y, tile.w, tile.h);
+        let stats = tracer.stats().since(&before, 0.0);
+        for (_, _, c) in img.pixels() {
+            for v in [c.r, c.g, c.b, c.a].iter() {
+                writer.write_all(&v.to_le_bytes())?;
+            }
+        }
+        for v in [stats.primary_rays, stats.shadow_rays, stats.reflection_rays, stats.intersection_tests].iter() {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+        writer.flush()?;
+    }
+}
+
+// tiles waiting for a worker and how many are being rendered, workers only
+// quit once both are zero since a failing worker hands its tile back
+struct Queue {
+    tiles: Vec<Tile>,
+    in_flight: usize
+}
+
+fn next_tile(queue: &(Mutex<Queue>, Condvar)) -> Option<Tile> {
+    let (lock, ready) = queue;
+    let mut q = lock.lock().unwrap();
+    loop {
+        if let Some(tile) = q.tiles.pop() {
+            q.in_flight += 1;
+            return Some(tile);
+        }
+        if q.in_flight == 0 {
+            return None;
+        }
+        q = ready.wait(q).unwrap();
+    }
+}
+
+fn tile_done(queue: &(Mutex<Queue>, Condvar), failed: Option<Tile>) {
+    let (lock, ready) = queue;
+    let mut q = lock.lock().unwrap();
+    q.in_flight -= 1;
+    q.tiles.extend(failed);
+    ready.notify_all();
+}
+
+fn render_remote(reader: &mut BufReader<TcpStream>, writer: &mut BufWriter<TcpStream>, tile: &Tile) -> io::Result<(Image, RenderStats)> {
+    writeln!(writer, "TILE {} {} {} {} {}", tile.frame, tile.x, tile.y, tile.w, tile.h)?;
+    writer.flush()?;
+    let mut img = Image::new_rgba(tile.w, tile.h);
+    for (_, _, c) in img.pixels_mut() {
+        *c = Color::new(read_f64(reader)?, read_f64(reader)?, read_f64(reader)?);
+        c.a = read_f64(reader)?;
+    }
+    let stats = RenderStats {
+        primary_rays: read_u64(reader)?,
+        shadow_rays: read_u64(reader)?,
+        reflection_rays: read_u64(reader)?,
+        intersection_tests: read_u64(reader)?,
+        ..RenderStats::default()
+    };
+    Ok((img, stats))
+}
+
+// a worker that stops answering for longer than timeout is given up on like
+// one that hung up, its tile goes back into the queue
+fn work(address: &str, job: &Job, timeout: Duration, queue: &(Mutex<Queue>, Condvar), results: mpsc::Sender<(Tile, Image, RenderStats)>) -> io::Result<()> {
+    let socket = address.to_socket_addrs()?.next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address"))?;
+    let stream = TcpStream::connect_timeout(&socket, CONNECT_TIMEOUT)?;
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    writeln!(writer, "{}", job.header())?;
+    writer.flush()?;
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim() != "OK" {
+        return Err(io::Error::other(line.trim().to_string()));
+    }
+    while let Some(tile) = next_tile(queue) {
+        match render_remote(&mut reader, &mut writer, &tile) {
+            Ok((img, stats)) => {
+                // the coordinator only hangs up once it gave up on the render
+                if results.send((tile, img, stats)).is_err() {
+                    tile_done(queue, None);
+                    return Ok(());
+                }
+                tile_done(queue, None);
+            },
+            Err(e) => {
+                tile_done(queue, Some(tile));
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
+
+// splits renders of a named scene over worker processes, see serve
+#[derive(Clone, Debug)]
+pub struct Coordinator {
+    pub workers: Vec<String>,
+    pub scene: String,
+    pub w: usize,
+    pub h: usize,
+    pub tile_size: usize,
+    // the longest a worker may take for one tile, or a frame of an animation
+    pub timeout: Duration
+}
+
+impl Coordinator {
+
+    pub fn new(workers: &[String], scene: &str, w: usize, h: usize) -> Coordinator {
+        Coordinator {workers: workers.to_vec(), scene: scene.to_string(), w, h, tile_size: 64, timeout: Duration::from_secs(600)}
+    }
+
+    // a single frame is cut into square tiles, animations are handed out a
+    // frame at a time, in the order they are rendered
+    pub fn tiles(&self, frames: &[usize]) -> Vec<Tile> {
+        if frames.len() > 1 {
+            return frames.iter().map(|&frame| Tile {frame, x: 0, y: 0, w: self.w, h: self.h}).collect();
+        }
+        let size = self.tile_size.max(1);
+        let mut tiles = vec![];
+        for &frame in frames.iter() {
+            for y in (0 .. self.h).step_by(size) {
+                for x in (0 .. self.w).step_by(size) {
+                    tiles.push(Tile {frame, x, y, w: size.min(self.w - x), h: size.min(self.h - y)});
+                }
+            }
+        }
+        tiles
+    }
+
+    // renders the beauty image of the frames on the workers and hands each
+    // frame to done once its last tile is in, tiles of a worker that fails go
+    // to the others and the render only fails when none is left
+    pub fn render<F>(&self, frames: &[usize], settings: &RenderSettings, observer: &mut dyn Observer, mut done: F) -> io::Result<()>
+        where F: FnMut(usize, RenderPasses) -> io::Result<()> {
+        let start = Instant::now();
+        let job = Job {
+            scene: self.scene.clone(),
+            w: self.w,
+            h: self.h,
+            samples: settings.samples,
+            reflection_steps: settings.reflection_steps
+        };
+        let mut tiles = self.tiles(frames);
+        let total = tiles.len();
+        let mut left: HashMap<usize, usize> = HashMap::new();
+        for tile in tiles.iter() {
+            *left.entry(tile.frame).or_insert(0) += 1;
+        }
+        // workers pop from the back
+        tiles.reverse();
+        let queue = (Mutex::new(Queue {tiles, in_flight: 0}), Condvar::new());
+        let mut images: HashMap<usize, Image> = HashMap::new();
+        let mut stats = RenderStats::default();
+        thread::scope(|scope| -> io::Result<()> {
+            let (sender, results) = mpsc::channel();
+            for address in self.workers.iter() {
+                let (job, queue, sender) = (&job, &queue, sender.clone());
+                scope.spawn(move || {
+                    if let Err(e) = work(address, job, self.timeout, queue, sender) {
+                        eprintln!("worker {}: {}", address, e);
+                    }
+                });
+            }
+            drop(sender);
+            let mut receive = || -> io::Result<()> {
+                for received in 0 .. total {
+                    let (tile, img, tile_stats) = results.recv().map_err(|_| {
+                        io::Error::other(format!("no worker left with {} tiles to go", total - received))
+                    })?;
+                    stats.add(&tile_stats);
+                    images.entry(tile.frame)
+                        .or_insert_with(|| Image::new_rgba(self.w, self.h))
+                        .paste(tile.x, tile.y, &img);
+                    observer.progress(&Progress {done: received + 1, total: Some(total), unit: Unit::Tiles, seconds: start.elapsed().as_secs_f64(), noise: None});
+                    let remaining = left.get_mut(&tile.frame).unwrap();
+                    *remaining -= 1;
+                    if *remaining == 0 {
+                        let mut out = RenderPasses::new(self.w, self.h, &[]);
+                        out.beauty = images.remove(&tile.frame).unwrap();
+                        settings.finish(&mut out);
+                        done(tile.frame, out)?;
+                    }
+                }
+                Ok(())
+            };
+            // the workers stop after their current tile instead of rendering
+            // the rest for nothing, the scope waits for them
+            let received = receive();
+            if received.is_err() {
+                queue.0.lock().unwrap().tiles.clear();
+            }
+            received
+        })?;
+        stats.seconds = start.elapsed().as_secs_f64();
+        observer.finished(&stats);
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worker() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || serve(listener));
+        address
+    }
+
+    #[test]
+    fn tiles_match_a_local_render() {
+        let settings = RenderSettings {samples: Some(2), ..RenderSettings::default()};
+        let local = scenes::by_name("reflections", 40.0, 30.0).unwrap().render_with(&settings);
+        let mut coordinator = Coordinator::new(&[worker(), worker()], "reflections", 40, 30);
+        coordinator.tile_size = 16;
+        let mut rendered = vec![];
+        coordinator.render(&[0], &settings, &mut Quiet, |frame, out| {
+            rendered.push((frame, out));
+            Ok(())
+        }).unwrap();
+        assert_eq!(rendered.len(), 1);
+        for ((_, _, a), (_, _, b)) in rendered[0].1.beauty.pixels().zip(local.beauty.pixels()) {
+            assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+        }
+    }
+
+    #[test]
+    fn survives_a_dead_worker() {
+        // nothing listens on a port that was just freed
+        let dead = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        let coordinator = Coordinator::new(&[dead, worker()], "room", 16, 8);
+        let mut frames = vec![];
+        coordinator.render(&[3, 4], &RenderSettings::default(), &mut Quiet, |frame, _| {
+            frames.push(frame);
+            Ok(())
+        }).unwrap();
+        assert_eq!(frames, vec![3, 4]);
+    }
+
+    #[test]
+    fn survives_a_hung_worker() {
+        // takes the job and a tile and then never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let hung = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            writeln!(&stream, "OK").unwrap();
+            // until the coordinator hangs up
+            io::copy(&mut reader, &mut io::sink()).ok();
+        });
+        let mut coordinator = Coordinator::new(&[hung, worker()], "room", 16, 8);
+        coordinator.timeout = Duration::from_millis(200);
+        let mut frames = vec![];
+        coordinator.render(&[3, 4, 5], &RenderSettings::default(), &mut Quiet, |frame, _| {
+            frames.push(frame);
+            Ok(())
+        }).unwrap();
+        frames.sort();
+        assert_eq!(frames, vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn unknown_scene() {
+        let coordinator = Coordinator::new(&[worker()], "teapot", 16, 8);
+        assert!(coordinator.render(&[0], &RenderSettings::default(), &mut Quiet, |_, _| Ok(())).is_err());
+    }
+
+}
@@ -0,0 +1,328 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use crate::image::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExrPixel {
+    Half,
+    Float
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExrCompression {
+    Uncompressed,
+    // zlib over blocks of 16 scanlines
+    Zip
+}
+
+impl ExrPixel {
+    fn type_id(self) -> i32 {
+        match self {
+            ExrPixel::Half => 1,
+            ExrPixel::Float => 2
+        }
+    }
+    fn size(self) -> usize {
+        match self {
+            ExrPixel::Half => 2,
+            ExrPixel::Float => 4
+        }
+    }
+}
+
+impl ExrCompression {
+    fn type_id(self) -> u8 {
+        match self {
+            ExrCompression::Uncompressed => 0,
+            ExrCompression::Zip => 3
+        }
+    }
+    fn lines_per_block(self) -> usize {
+        match self {
+            ExrCompression::Uncompressed => 1,
+            ExrCompression::Zip => 16
+        }
+    }
+}
+
+// round to nearest even, overflow goes to infinity, tiny values to
+// subnormals or zero
+pub fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+    if exp == 0xff {
+        let nan = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let e = exp - 127 + 15;
+    if e >= 0x1f {
+        sign | 0x7c00
+    } else if e <= 0 {
+        if e < -10 {
+            return sign;
+        }
+        let m = mantissa | 0x0080_0000;
+        let shift = (14 - e) as u32;
+        let half = m >> shift;
+        let rest = m & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round = rest > halfway || (rest == halfway && half & 1 == 1);
+        sign | (half + round as u32) as u16
+    } else {
+        let half = ((e as u32) << 10) | (mantissa >> 13);
+        let rest = mantissa & 0x1fff;
+        let round = rest > 0x1000 || (rest == 0x1000 && half & 1 == 1);
+        // a carry out of the mantissa correctly bumps the exponent
+        sign | (half + round as u32) as u16
+    }
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+// one named channel pulled out of an image
+pub struct Channel<'a> {
+    pub name: String,
+    image: &'a Image,
+    value: fn(&Color) -> f64,
+    pixel_type: ExrPixel
+}
+
+impl<'a> Channel<'a> {
+    pub fn new(name: &str, image: &'a Image, value: fn(&Color) -> f64, pixel_type: ExrPixel) -> Channel<'a> {
+        Channel {name: name.to_string(), image, value, pixel_type}
+    }
+}
+
+fn header(w: usize, h: usize, channels: &[Channel], compression: ExrCompression) -> Vec<u8> {
+    let mut header = vec![];
+    header.extend_from_slice(&20000630i32.to_le_bytes());
+    header.extend_from_slice(&2i32.to_le_bytes());
+    let mut list = vec![];
+    for channel in channels.iter() {
+        list.extend_from_slice(channel.name.as_bytes());
+        list.push(0);
+        list.extend_from_slice(&channel.pixel_type.type_id().to_le_bytes());
+        list.extend_from_slice(&[0, 0, 0, 0]);
+        list.extend_from_slice(&1i32.to_le_bytes());
+        list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    list.push(0);
+    let mut window = vec![];
+    for v in [0, 0, w as i32 - 1, h as i32 - 1].iter() {
+        window.extend_from_slice(&v.to_le_bytes());
+    }
+    attribute(&mut header, "channels", "chlist", &list);
+    attribute(&mut header, "compression", "compression", &[compression.type_id()]);
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut header, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    let mut center = vec![];
+    center.extend_from_slice(&0.0f32.to_le_bytes());
+    center.extend_from_slice(&0.0f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &center);
+    attribute(&mut header, "screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    header.push(0);
+    header
+}
+
+fn scanline(channels: &[Channel], w: usize, y: usize, out: &mut Vec<u8>) {
+    for channel in channels.iter() {
+        for x in 0 .. w {
+            let v = (channel.value)(channel.image.pixel(x, y)) as f32;
+            match channel.pixel_type {
+                ExrPixel::Half => out.extend_from_slice(&f32_to_half(v).to_le_bytes()),
+                ExrPixel::Float => out.extend_from_slice(&v.to_le_bytes())
+            }
+        }
+    }
+}
+
+// byte split and delta predictor of the zip codec
+fn predict(raw: &[u8]) -> Vec<u8> {
+    let half = raw.len().div_ceil(2);
+    let mut split = vec![0u8; raw.len()];
+    for (i, b) in raw.iter().enumerate() {
+        if i % 2 == 0 {
+            split[i / 2] = *b;
+        } else {
+            split[half + i / 2] = *b;
+        }
+    }
+    let mut prev = split[0] as i32;
+    for b in split.iter_mut().skip(1) {
+        let current = *b as i32;
+        *b = (current - prev + 128 + 256) as u8;
+        prev = current;
+    }
+    split
+}
+
+fn zip(raw: &[u8]) -> Vec<u8> {
+    deflate::deflate_bytes_zlib(&predict(raw))
+}
+
+pub fn write_exr(img: &Image, file: &str, pixel_type: ExrPixel, compression: ExrCompression) -> io::Result<()> {
+    let channels = vec![
+        Channel::new("B", img, |c| c.b, pixel_type),
+        Channel::new("G", img, |c| c.g, pixel_type),
+        Channel::new("R", img, |c| c.r, pixel_type)
+    ];
+    write_channels(file, img.w, img.h, channels, compression)
+}
+
+// channels may come from different images of the same size
+pub fn write_channels(file: &str, w: usize, h: usize, mut channels: Vec<Channel>, compression: ExrCompression) -> io::Result<()> {
+    // readers expect the channels in alphabetical order
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+    let header = header(w, h, &channels, compression);
+    let lines = compression.lines_per_block();
+    let line_size: usize = channels.iter().map(|c| w * c.pixel_type.size()).sum();
+    let mut chunks = vec![];
+    for y0 in (0 .. h).step_by(lines) {
+        let mut raw = Vec::with_capacity(lines * line_size);
+        for y in y0 .. usize::min(y0 + lines, h) {
+            scanline(&channels, w, y, &mut raw);
+        }
+        let data = match compression {
+            ExrCompression::Uncompressed => raw,
+            ExrCompression::Zip => {
+                // blocks that do not shrink are stored as they are
+                let compressed = zip(&raw);
+                if compressed.len() < raw.len() { compressed } else { raw }
+            }
+        };
+        let mut chunk = vec![];
+        chunk.extend_from_slice(&(y0 as i32).to_le_bytes());
+        chunk.extend_from_slice(&(data.len() as i32).to_le_bytes());
+        chunk.extend_from_slice(&data);
+        chunks.push(chunk);
+    }
+    let mut w = BufWriter::new(File::create(file)?);
+    w.write_all(&header)?;
+    let mut offset = (header.len() + chunks.len() * 8) as u64;
+    for chunk in chunks.iter() {
+        w.write_all(&offset.to_le_bytes())?;
+        offset += chunk.len() as u64;
+    }
+    for chunk in chunks.iter() {
+        w.write_all(chunk)?;
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::convert::TryInto;
+
+    #[test]
+    fn half_floats() {
+        let cases: [(f32, u16); 16] = [
+            (0.0, 0x0000), (-0.0, 0x8000), (1.0, 0x3c00), (-2.0, 0xc000), (65504.0, 0x7bff),
+            // halfway between 1 and its neighbours rounds to the even one
+            (1.0 + 1.0 / 2048.0, 0x3c00), (1.0 + 3.0 / 2048.0, 0x3c02),
+            // the smallest normal and subnormals, ties again to even
+            (6.103_515_6e-5, 0x0400), (5.960_464_5e-8, 0x0001), (2.980_232_2e-8, 0x0000), (8.940_697e-8, 0x0002),
+            (1e-10, 0x0000),
+            // past the largest half, 65520 is halfway and rounds up
+            (65520.0, 0x7c00), (1e6, 0x7c00), (f32::INFINITY, 0x7c00), (f32::NEG_INFINITY, 0xfc00)
+        ];
+        for (value, half) in cases.iter() {
+            assert_eq!(f32_to_half(*value), *half, "{}", value);
+        }
+        assert_eq!(f32_to_half(f32::NAN) & 0x7c00, 0x7c00);
+        assert_ne!(f32_to_half(f32::NAN) & 0x03ff, 0);
+    }
+
+    #[test]
+    fn predictor_splits_and_deltas() {
+        assert_eq!(predict(&[1, 2, 3, 4]), vec![1, 130, 127, 130]);
+        // undone the way readers do it
+        let raw: Vec<u8> = (0 .. 101u32).map(|i| (i * i * 7 % 251) as u8).collect();
+        let mut split = predict(&raw);
+        for i in 1 .. split.len() {
+            split[i] = (split[i - 1] as i32 + split[i] as i32 - 128) as u8;
+        }
+        let half = raw.len().div_ceil(2);
+        let undone: Vec<u8> = (0 .. raw.len()).map(|i| if i % 2 == 0 { split[i / 2] } else { split[half + i / 2] }).collect();
+        assert_eq!(undone, raw);
+    }
+
+    fn read_i32(bytes: &[u8], at: usize) -> i32 {
+        i32::from_le_bytes(bytes[at .. at + 4].try_into().unwrap())
+    }
+
+    fn read_string(bytes: &[u8], at: &mut usize) -> String {
+        let end = *at + bytes[*at ..].iter().position(|b| *b == 0).unwrap();
+        let s = String::from_utf8(bytes[*at .. end].to_vec()).unwrap();
+        *at = end + 1;
+        s
+    }
+
+    #[test]
+    fn header_and_offset_table() {
+        let (w, h) = (37, 40);
+        let mut img = Image::new_rgba(w, h);
+        for (x, y, c) in img.pixels_mut() {
+            *c = Color::new(x as f64 / w as f64, y as f64 / h as f64, 0.5);
+        }
+        let file = std::env::temp_dir().join(format!("raytracer_exr_test_{}.exr", std::process::id()));
+        let file = file.to_str().unwrap();
+        write_exr(&img, file, ExrPixel::Half, ExrCompression::Zip).unwrap();
+        let bytes = std::fs::read(file).unwrap();
+        std::fs::remove_file(file).unwrap();
+        assert_eq!((read_i32(&bytes, 0), read_i32(&bytes, 4)), (20000630, 2));
+        let mut at = 8;
+        let mut attributes = HashMap::new();
+        loop {
+            let name = read_string(&bytes, &mut at);
+            if name.is_empty() {
+                break;
+            }
+            let kind = read_string(&bytes, &mut at);
+            let size = read_i32(&bytes, at) as usize;
+            attributes.insert(name, (kind, bytes[at + 4 .. at + 4 + size].to_vec()));
+            at += 4 + size;
+        }
+        assert_eq!(attributes["compression"], ("compression".to_string(), vec![3]));
+        let window: Vec<i32> = (0 .. 4).map(|i| read_i32(&attributes["dataWindow"].1, 4 * i)).collect();
+        assert_eq!(window, vec![0, 0, w as i32 - 1, h as i32 - 1]);
+        let list = &attributes["channels"].1;
+        let mut names = vec![];
+        let mut c = 0;
+        while list[c] != 0 {
+            names.push(read_string(list, &mut c));
+            assert_eq!(read_i32(list, c), 1);
+            c += 16;
+        }
+        assert_eq!(names, vec!["B", "G", "R"]);
+        for name in ["lineOrder", "displayWindow", "pixelAspectRatio", "screenWindowCenter", "screenWindowWidth"].iter() {
+            assert!(attributes.contains_key(*name), "{}", name);
+        }
+        // blocks of 16 lines, each offset at the start of its chunk and the
+        // chunks back to back up to the end of the file
+        let blocks = h.div_ceil(16);
+        let offsets: Vec<usize> = (0 .. blocks)
+            .map(|i| u64::from_le_bytes(bytes[at + 8 * i .. at + 8 * i + 8].try_into().unwrap()) as usize)
+            .collect();
+        assert_eq!(offsets[0], at + 8 * blocks);
+        for (i, offset) in offsets.iter().enumerate() {
+            assert_eq!(read_i32(&bytes, *offset), 16 * i as i32);
+            let end = offset + 8 + read_i32(&bytes, offset + 4) as usize;
+            assert_eq!(end, if i + 1 < blocks { offsets[i + 1] } else { bytes.len() });
+        }
+    }
+
+}
@@ -1,42 +1,76 @@
-use crate::transformations::Transformation;
-use crate::tuples::Tuple4D;
+use crate::transformations::{Transformation, AnimatedTransformation};
+use crate::tuples::*;
 use crate::material::Material;
+use crate::bounds::Bounds;
+use std::cell::Cell;
+
+thread_local! {
+    // basic shapes intersected on this thread, composites only pass the ray
+    // on and are not counted
+    static SHAPE_TESTS: Cell<u64> = const { Cell::new(0) };
+}
+
+// the world takes the difference around each ray for its statistics
+pub fn shape_tests() -> u64 {
+    SHAPE_TESTS.with(|n| n.get())
+}
+
+fn count_shape_test() {
+    SHAPE_TESTS.with(|n| n.set(n.get() + 1));
+}
 
 #[derive(Debug)]
 pub struct Ray {
-    origin: Tuple4D,
-    dir: Tuple4D
+    pub origin: Point3,
+    pub dir: Vector3,
+    pub time: f64
 }
 
 impl Ray {
 
-    pub fn new(origin: Tuple4D, dir: Tuple4D) -> Ray {
-        assert!(origin.is_point() && dir.is_vec());
-        Ray {origin, dir}
+    pub fn new(origin: Point3, dir: Vector3) -> Ray {
+        Ray::new_at(origin, dir, 0.0)
+    }
+
+    pub fn new_at(origin: Point3, dir: Vector3, time: f64) -> Ray {
+        Ray {origin, dir, time}
     }
 
-    pub fn position(&self, dist: f64) -> Tuple4D {
-        self.origin.add(&self.dir.scale(dist))
+    pub fn position(&self, dist: f64) -> Point3 {
+        self.origin + self.dir * dist
     }
 
     pub fn transform(&self, transformation: &Transformation) -> Ray {
         let origin = transformation.transform(&self.origin);
         let direction = transformation.transform(&self.dir);
-        Ray::new(origin, direction)
+        Ray::new_at(origin, direction, self.time)
     }
 
 }
 
+// which primitive was hit, the index the world gave the top level object
+// and for composite primitives the child indices leading down to the part
+// that was hit, innermost first
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Handle {
+    pub object: usize,
+    pub path: Vec<usize>
+}
+
 #[derive(Clone, Debug)]
 pub struct Intersection {
     pub dist: f64,
-    pub object_id: usize
+    pub handle: Handle
 }
 
 impl Intersection {
 
+    pub fn new(dist: f64) -> Intersection {
+        Intersection {dist, handle: Handle::default()}
+    }
+
     pub fn hit(intersections: &[Intersection]) -> Option<Intersection> {
-        if intersections.len() == 0 {
+        if intersections.is_empty() {
             None
         } else {
             let mut offset = 0;
@@ -58,49 +92,72 @@ impl Intersection {
 
 pub struct IntersectionPrecomp {
     pub intersection: Intersection,
-    pub point: Tuple4D,
-    pub eye: Tuple4D,
-    pub normal: Tuple4D,
+    pub point: Point3,
+    pub eye: Vector3,
+    pub normal: Normal3,
     pub hit_inside: bool,
-    pub reflection: Tuple4D
+    pub reflection: Vector3,
+    pub time: f64
 }
 
 impl IntersectionPrecomp {
 
-    pub fn new(intersection: &Intersection, ray: &Ray, shape: &Primitive) -> IntersectionPrecomp {
+    pub fn new(intersection: &Intersection, ray: &Ray, shape: &dyn Primitive) -> IntersectionPrecomp {
         let point = ray.position(intersection.dist);
-        let eye = ray.dir.scale(-1.0);
-        let normal = shape.surface_normal(&point);
+        let eye = -ray.dir;
+        let normal = shape.normal_along(&point, &intersection.handle.path, ray.time);
         let intersection = intersection.clone();
         let reflection = reflect(&ray.dir, &normal); 
         let hit_inside = normal.dot(&eye) < 0.0;        
+        let time = ray.time;
         if hit_inside {
-            let normal = normal.scale(-1.0);
-            IntersectionPrecomp{intersection, point, eye, normal, hit_inside, reflection}
+            let normal = -normal;
+            IntersectionPrecomp{intersection, point, eye, normal, hit_inside, reflection, time}
         } else {
-            IntersectionPrecomp{intersection, point, eye, normal, hit_inside, reflection}
+            IntersectionPrecomp{intersection, point, eye, normal, hit_inside, reflection, time}
         }
     }
 
 }
 
-pub trait Primitive {    
-    fn object_id(&self) -> usize;
+// Send and Sync so instances can share shapes behind an Arc
+pub trait Primitive: Send + Sync {
     fn intersect(&self, ray: &Ray) -> Vec<Intersection>;
-    fn surface_normal(&self, p: &Tuple4D) -> Tuple4D;
+    fn surface_normal(&self, p: &Point3) -> Normal3;
     fn material(&self) -> Material;
+    fn surface_normal_at(&self, p: &Point3, _time: f64) -> Normal3 {
+        self.surface_normal(p)
+    }
+    // composite primitives follow the path of an intersection to the part
+    // that was hit, everything else has a single surface and material
+    fn normal_along(&self, p: &Point3, _path: &[usize], time: f64) -> Normal3 {
+        self.surface_normal_at(p, time)
+    }
+    fn material_along(&self, _path: &[usize]) -> Material {
+        self.material()
+    }
+    // whether the ray comes from inside the solid, closed shapes are left
+    // behind at minus infinity so only half spaces ever start inside
+    fn starts_inside(&self, _ray: &Ray) -> bool {
+        false
+    }
+    // a box around the shape in the space of whatever contains it
+    fn bounds(&self) -> Bounds {
+        Bounds::infinite()
+    }
 }
 
 pub struct UnitSphere {
-    id: usize,
     transformation: Transformation,
+    inverse: Transformation,
     material: Material
 }
 
 impl UnitSphere {
 
-    pub fn new(id: usize, transformation: Transformation, material: Material) -> UnitSphere {
-        UnitSphere{id, transformation, material}
+    pub fn new(transformation: Transformation, material: Material) -> UnitSphere {
+        let inverse = transformation.inverse().unwrap();
+        UnitSphere{transformation, inverse, material}
     }
 
 }
@@ -111,13 +168,10 @@ impl Primitive for UnitSphere {
         self.material.clone()
     }
 
-    fn object_id(&self) -> usize {
-        self.id
-    } 
-
     fn intersect(&self, ray: &Ray) -> Vec<Intersection> {
-        let r = ray.transform(&self.transformation.inverse().unwrap());
-        let sphere2ray = r.origin.sub(&Tuple4D::new_point(0.0, 0.0, 0.0));
+        count_shape_test();
+        let r = ray.transform(&self.inverse);
+        let sphere2ray = r.origin - Point3::origin();
         let a = r.dir.dot(&r.dir);
         let b = 2.0 * r.dir.dot(&sphere2ray);
         let c = sphere2ray.dot(&sphere2ray) - 1.0;
@@ -127,60 +181,230 @@ impl Primitive for UnitSphere {
         } else {
             let t1 = (-b - f64::sqrt(discriminant)) / (2.0 * a);
             let t2 = (-b + f64::sqrt(discriminant)) / (2.0 * a);            
-            vec![Intersection{dist: t1, object_id: self.id}, Intersection{dist: t2, object_id: self.id}] 
+            vec![Intersection::new(t1), Intersection::new(t2)] 
         }
     }
 
-    fn surface_normal(&self, world_point: &Tuple4D) -> Tuple4D {
-        let object_point = self.transformation.inverse().unwrap().transform(&world_point);
-        let object_normal = object_point.sub(&Tuple4D::new_point(0.0, 0.0, 0.0));
-        let mut world_normal = self.transformation.inverse().unwrap().transpose().transform(&object_normal);
-        world_normal.w = 0.0;
-        world_normal.normalized()
+    fn surface_normal(&self, world_point: &Point3) -> Normal3 {
+        let object_point = self.inverse.transform(world_point);
+        let object_normal = Normal3::from(object_point - Point3::origin());
+        self.transformation.transform(&object_normal).normalized()
+    }
+
+    fn bounds(&self) -> Bounds {
+        Bounds::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0)).transform(&self.transformation)
     }
 
 }
 
-pub struct Plane { 
-    id: usize,
+pub struct Plane {
     transformation: Transformation,
+    inverse: Transformation,
     material: Material
 }
 
 impl Plane {
 
-    pub fn new(id: usize, transformation: Transformation, material: Material) -> Plane {
-        Plane{id, transformation, material}
+    pub fn new(transformation: Transformation, material: Material) -> Plane {
+        let inverse = transformation.inverse().unwrap();
+        Plane{transformation, inverse, material}
     }
 
 }
 
 impl Primitive for Plane {
-    fn object_id(&self) -> usize {
-        self.id
-    }
     fn intersect(&self, ray: &Ray) -> Vec<Intersection> {
-        let r = ray.transform(&self.transformation.inverse().unwrap());
+        count_shape_test();
+        let r = ray.transform(&self.inverse);
         if r.dir.y.abs() < 1e-08 {
             vec![]
         } else {
             let t = -r.origin.y / r.dir.y;
-            vec![Intersection{dist: t, object_id: self.id}]
+            vec![Intersection::new(t)]
         }
     }
-    fn surface_normal(&self, _world_point: &Tuple4D) -> Tuple4D {
-        let object_normal = Tuple4D::new_vector(0.0, 1.0, 0.0);
-        let mut world_normal = self.transformation.inverse().unwrap().transpose().transform(&object_normal);
-        world_normal.w = 0.0;
-        world_normal.normalized()
+    fn surface_normal(&self, _world_point: &Point3) -> Normal3 {
+        let object_normal = Normal3::new(0.0, 1.0, 0.0);
+        self.transformation.transform(&object_normal).normalized()
     }
     fn material(&self) -> Material {
         self.material.clone()
     }
+    // the solid side is below the plane, away from its normal, a ray that
+    // never crosses it is inside all along or not at all
+    fn starts_inside(&self, ray: &Ray) -> bool {
+        let r = ray.transform(&self.inverse);
+        if r.dir.y.abs() < 1e-08 {
+            return r.origin.y < 0.0;
+        }
+        r.dir.y > 0.0
+    }
+}
+
+pub struct Animated {
+    motion: AnimatedTransformation,
+    shape: Box<dyn Primitive>
+}
+
+impl Animated {
+
+    pub fn new(motion: AnimatedTransformation, shape: Box<dyn Primitive>) -> Animated {
+        Animated{motion, shape}
+    }
+
+}
+
+// a keyframe scaled to nothing, as objects popping in start out, flattens
+// the shape away so rays at that time pass right through
+impl Primitive for Animated {
+    fn intersect(&self, ray: &Ray) -> Vec<Intersection> {
+        match self.motion.at(ray.time).inverse() {
+            Some(inverse) => self.shape.intersect(&ray.transform(&inverse)),
+            None => vec![]
+        }
+    }
+    fn surface_normal(&self, world_point: &Point3) -> Normal3 {
+        self.surface_normal_at(world_point, 0.0)
+    }
+    fn surface_normal_at(&self, world_point: &Point3, time: f64) -> Normal3 {
+        self.normal_along(world_point, &[], time)
+    }
+    fn normal_along(&self, world_point: &Point3, path: &[usize], time: f64) -> Normal3 {
+        let transformation = self.motion.at(time);
+        match transformation.inverse() {
+            Some(inverse) => {
+                let object_normal = self.shape.normal_along(&inverse.transform(world_point), path, time);
+                transformation.transform(&object_normal).normalized()
+            },
+            // never hit, so never asked for in a render
+            None => self.shape.normal_along(world_point, path, time)
+        }
+    }
+    fn material(&self) -> Material {
+        self.shape.material()
+    }
+    fn material_along(&self, path: &[usize]) -> Material {
+        self.shape.material_along(path)
+    }
+    fn starts_inside(&self, ray: &Ray) -> bool {
+        self.motion.at(ray.time).inverse().is_some_and(|inverse| self.shape.starts_inside(&ray.transform(&inverse)))
+    }
 }
 
-pub fn reflect(input: &Tuple4D, normal: &Tuple4D) -> Tuple4D {
-    return input.sub(&normal.scale(2.0 * input.dot(normal)))
+pub fn reflect(input: &Vector3, normal: &Normal3) -> Vector3 {
+    *input - normal.to_vector() * (2.0 * normal.dot(input))
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Color;
+    use crate::transformations::Keyframe;
+    use crate::quaternion::Quaternion;
+
+    fn sphere(transformation: Transformation) -> UnitSphere {
+        UnitSphere::new(transformation, Material::from_color(Color::new(1.0, 1.0, 1.0)))
+    }
+
+    fn dists(intersections: &[Intersection]) -> Vec<f64> {
+        intersections.iter().map(|i| i.dist).collect()
+    }
+
+    #[test]
+    fn ray_through_sphere() {
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(dists(&sphere(Transformation::identity()).intersect(&r)), vec![4.0, 6.0]);
+    }
+
+    #[test]
+    fn ray_tangent_to_sphere() {
+        let r = Ray::new(Point3::new(0.0, 1.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(dists(&sphere(Transformation::identity()).intersect(&r)), vec![5.0, 5.0]);
+    }
+
+    #[test]
+    fn ray_misses_sphere() {
+        let r = Ray::new(Point3::new(0.0, 2.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert!(sphere(Transformation::identity()).intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn ray_inside_and_behind_sphere() {
+        let s = sphere(Transformation::identity());
+        let inside = Ray::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(dists(&s.intersect(&inside)), vec![-1.0, 1.0]);
+        let behind = Ray::new(Point3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(dists(&s.intersect(&behind)), vec![-6.0, -4.0]);
+    }
+
+    #[test]
+    fn ray_through_transformed_sphere() {
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        let scaled = sphere(Transformation::scale(Vector3::new(2.0, 2.0, 2.0)));
+        assert_eq!(dists(&scaled.intersect(&r)), vec![3.0, 7.0]);
+        let moved = sphere(Transformation::translation(Vector3::new(5.0, 0.0, 0.0)));
+        assert!(moved.intersect(&r).is_empty());
+    }
+
+    fn animated(keyframes: Vec<(f64, Vector3, f64)>) -> Animated {
+        let keyframes = keyframes.into_iter()
+            .map(|(time, at, size)| Keyframe::new(time, at, Quaternion::identity(), Vector3::new(size, size, size)))
+            .collect();
+        Animated::new(AnimatedTransformation::new(keyframes), Box::new(sphere(Transformation::identity())))
+    }
+
+    #[test]
+    fn motion_blur_follows_the_ray_time() {
+        let moving = animated(vec![(0.0, Vector3::new(0.0, 0.0, 0.0), 1.0), (1.0, Vector3::new(10.0, 0.0, 0.0), 1.0)]);
+        let dir = Vector3::new(0.0, 0.0, 1.0);
+        assert!(moving.intersect(&Ray::new_at(Point3::new(5.0, 0.0, -5.0), dir, 0.0)).is_empty());
+        assert_eq!(dists(&moving.intersect(&Ray::new_at(Point3::new(5.0, 0.0, -5.0), dir, 0.5))), vec![4.0, 6.0]);
+        let ray = Ray::new_at(Point3::new(5.5, 0.0, -5.0), dir, 0.5);
+        let xs = moving.intersect(&ray);
+        let precomp = IntersectionPrecomp::new(&xs[0], &ray, &moving);
+        assert_eq!(precomp.time, 0.5);
+        let n = precomp.normal;
+        assert!((n.x - 0.5).abs() < 1e-9 && n.y.abs() < 1e-9 && (n.z + f64::sqrt(0.75)).abs() < 1e-9, "{:?}", n);
+    }
+
+    #[test]
+    fn shapes_scaled_to_nothing_are_not_hit() {
+        let popping = animated(vec![(0.0, Vector3::new(0.0, 0.0, 0.0), 0.0), (1.0, Vector3::new(0.0, 0.0, 0.0), 1.0)]);
+        let at = |time| Ray::new_at(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0), time);
+        assert!(popping.intersect(&at(0.0)).is_empty());
+        assert!(!popping.starts_inside(&at(0.0)));
+        assert_eq!(dists(&popping.intersect(&at(0.5))), vec![4.5, 5.5]);
+        assert_eq!(dists(&popping.intersect(&at(1.0))), vec![4.0, 6.0]);
+    }
+
+    #[test]
+    fn plane_starts_inside_below() {
+        let p = Plane::new(Transformation::identity(), Material::from_color(Color::new(1.0, 1.0, 1.0)));
+        let up = Ray::new(Point3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        assert!(p.starts_inside(&up));
+        // parallel rays never cross so only their height matters, also when
+        // they are just short of parallel
+        for dy in [0.0, 1e-10, -1e-10].iter() {
+            let below = Ray::new(Point3::new(0.0, -1.0, 0.0), Vector3::new(1.0, *dy, 0.0));
+            assert!(p.intersect(&below).is_empty() && p.starts_inside(&below));
+            let above = Ray::new(Point3::new(0.0, 1.0, 0.0), Vector3::new(1.0, *dy, 0.0));
+            assert!(!p.starts_inside(&above));
+        }
+    }
+
+    fn hit_dist(dists: &[f64]) -> Option<f64> {
+        let intersections: Vec<Intersection> = dists.iter().map(|d| Intersection::new(*d)).collect();
+        Intersection::hit(&intersections).map(|i| i.dist)
+    }
+
+    #[test]
+    fn hit_is_nearest_non_negative() {
+        assert_eq!(hit_dist(&[1.0, 2.0]), Some(1.0));
+        assert_eq!(hit_dist(&[-1.0, 1.0]), Some(1.0));
+        assert_eq!(hit_dist(&[-2.0, -1.0]), None);
+        assert_eq!(hit_dist(&[]), None);
+    }
+
+}
@@ -0,0 +1,68 @@
+// renders every canonical scene at a low resolution and compares it against
+// the reference in tests/golden, run with UPDATE_GOLDEN=1 to accept changes
+use std::env;
+use std::path::PathBuf;
+use crate::image::Image;
+use crate::diff::compare;
+use crate::scenes;
+
+const WIDTH: f64 = 64.0;
+const HEIGHT: f64 = 48.0;
+// the references went through 8 bit quantization, so they are never exact
+const MIN_PSNR: f64 = 40.0;
+const MAX_FLIP: f64 = 0.01;
+
+fn reference(name: &str) -> String {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests");
+    path.push("golden");
+    path.push(format!("{}.png", name));
+    path.to_string_lossy().into_owned()
+}
+
+fn check(name: &str) {
+    let img = scenes::by_name(name, WIDTH, HEIGHT).unwrap().trace(5);
+    let file = reference(name);
+    if env::var("UPDATE_GOLDEN").is_ok() {
+        img.write(&file).unwrap();
+        return;
+    }
+    let expected = Image::read(&file).unwrap_or_else(|e| panic!("missing reference {}: {}", file, e));
+    let diff = compare(&img, &expected).unwrap();
+    if diff.psnr < MIN_PSNR || diff.flip > MAX_FLIP {
+        let failed = file.replace(".png", ".failed.png");
+        img.write(&failed).unwrap();
+        diff.heatmap().write(&file.replace(".png", ".diff.png")).unwrap();
+        panic!("{} differs from its reference: psnr {:.2} dB, flip {:.4}, see {}", name, diff.psnr, diff.flip, failed);
+    }
+}
+
+#[test]
+fn default_world() {
+    check("default");
+}
+
+#[test]
+fn room() {
+    check("room");
+}
+
+#[test]
+fn cornell_box() {
+    check("cornell");
+}
+
+#[test]
+fn reflections() {
+    check("reflections");
+}
+
+#[test]
+fn csg() {
+    check("csg");
+}
+
+#[test]
+fn forest() {
+    check("forest");
+}
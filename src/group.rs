@@ -0,0 +1,174 @@
+use crate::tuples::*;
+use crate::transformations::*;
+use crate::geometry::*;
+use crate::material::Material;
+use crate::bounds::Bounds;
+use crate::bvh::Bvh;
+
+// children placed in the group's own space, so moving the group moves
+// everything in it, groups nest to any depth
+pub struct Group {
+    transformation: Transformation,
+    inverse: Transformation,
+    children: Vec<Box<dyn Primitive>>,
+    // around the children and of each of them, in group space
+    bounds: Bounds,
+    bvh: Bvh
+}
+
+impl Group {
+
+    pub fn new(transformation: Transformation, children: Vec<Box<dyn Primitive>>) -> Group {
+        let inverse = transformation.inverse().unwrap();
+        let boxes: Vec<Bounds> = children.iter().map(|c| c.bounds()).collect();
+        let bounds = boxes.iter().fold(Bounds::empty(), |b, c| b.union(c));
+        let bvh = Bvh::new(boxes);
+        Group{transformation, inverse, children, bounds, bvh}
+    }
+
+    pub fn set_transformation(&mut self, transformation: Transformation) {
+        self.inverse = transformation.inverse().unwrap();
+        self.transformation = transformation;
+    }
+
+    pub fn children(&self) -> &[Box<dyn Primitive>] {
+        &self.children
+    }
+
+    pub fn world_to_object(&self, p: &Point3) -> Point3 {
+        self.inverse.transform(p)
+    }
+
+    pub fn normal_to_world(&self, n: &Normal3) -> Normal3 {
+        self.transformation.transform(n).normalized()
+    }
+
+    // the child the outermost step of the path leads to and the rest of it,
+    // none in an empty group
+    fn child<'a>(&'a self, path: &'a [usize]) -> Option<(&'a dyn Primitive, &'a [usize])> {
+        match path.split_last() {
+            Some((&i, rest)) => Some((self.children[i].as_ref(), rest)),
+            None => self.children.first().map(|c| (c.as_ref(), path))
+        }
+    }
+
+}
+
+impl Primitive for Group {
+
+    // the ray is taken into group space once for all children and only
+    // tested against those the hierarchy finds along it
+    fn intersect(&self, ray: &Ray) -> Vec<Intersection> {
+        let local = ray.transform(&self.inverse);
+        let mut intersections = vec![];
+        for i in self.bvh.candidates(&local) {
+            for mut intersection in self.children[i].intersect(&local) {
+                intersection.handle.path.push(i);
+                intersections.push(intersection);
+            }
+        }
+        intersections.sort_by(|a, b| a.dist.partial_cmp(&b.dist).unwrap());
+        intersections
+    }
+
+    fn surface_normal(&self, p: &Point3) -> Normal3 {
+        self.normal_along(p, &[], 0.0)
+    }
+
+    // an empty group is never hit, so its normal is never seen
+    fn normal_along(&self, p: &Point3, path: &[usize], time: f64) -> Normal3 {
+        match self.child(path) {
+            Some((child, rest)) => self.normal_to_world(&child.normal_along(&self.world_to_object(p), rest, time)),
+            None => self.normal_to_world(&Normal3::new(0.0, 1.0, 0.0))
+        }
+    }
+
+    fn material(&self) -> Material {
+        self.material_along(&[])
+    }
+
+    fn material_along(&self, path: &[usize]) -> Material {
+        match self.child(path) {
+            Some((child, rest)) => child.material_along(rest),
+            None => Material::default()
+        }
+    }
+
+    // as a csg operand a group is the union of its children
+    fn starts_inside(&self, ray: &Ray) -> bool {
+        let local = ray.transform(&self.inverse);
+        self.children.iter().any(|c| c.starts_inside(&local))
+    }
+
+    fn bounds(&self) -> Bounds {
+        self.bounds.transform(&self.transformation)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Color;
+
+    fn sphere(transformation: Transformation, color: Color) -> Box<dyn Primitive> {
+        Box::new(UnitSphere::new(transformation, Material::from_color(color)))
+    }
+
+    #[test]
+    fn intersects_children_in_group_space() {
+        let group = Group::new(Transformation::scale(Vector3::new(2.0, 2.0, 2.0)), vec![
+            sphere(Transformation::translation(Vector3::new(5.0, 0.0, 0.0)), Color::new(1.0, 0.0, 0.0)),
+            sphere(Transformation::identity(), Color::new(0.0, 1.0, 0.0))
+        ]);
+        let r = Ray::new(Point3::new(10.0, 0.0, -10.0), Vector3::new(0.0, 0.0, 1.0));
+        let xs = group.intersect(&r);
+        assert_eq!(xs.iter().map(|i| i.dist).collect::<Vec<f64>>(), vec![8.0, 12.0]);
+        assert!(xs.iter().all(|i| i.handle.path == vec![0]));
+        assert_eq!(group.material_along(&xs[0].handle.path).color.r, 1.0);
+    }
+
+    #[test]
+    fn normal_through_nested_groups() {
+        let inner = Group::new(Transformation::scale(Vector3::new(1.0, 2.0, 3.0)), vec![
+            sphere(Transformation::translation(Vector3::new(5.0, 0.0, 0.0)), Color::new(1.0, 1.0, 1.0))
+        ]);
+        let outer = Group::new(Transformation::rotate_y(90.0), vec![Box::new(inner)]);
+        let n = outer.normal_along(&Point3::new(1.7321, 1.1547, -5.5774), &[0, 0], 0.0);
+        assert!((n.x - 0.2857).abs() < 1e-4 && (n.y - 0.4286).abs() < 1e-4 && (n.z + 0.8571).abs() < 1e-4);
+    }
+
+    #[test]
+    fn moving_the_group_moves_its_children() {
+        let mut group = Group::new(Transformation::identity(), vec![
+            sphere(Transformation::identity(), Color::new(1.0, 1.0, 1.0))
+        ]);
+        let r = Ray::new(Point3::new(3.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert!(group.intersect(&r).is_empty());
+        group.set_transformation(Transformation::translation(Vector3::new(3.0, 0.0, 0.0)));
+        assert_eq!(group.intersect(&r).len(), 2);
+        assert_eq!(group.bounds(), Bounds::new(Point3::new(2.0, -1.0, -1.0), Point3::new(4.0, 1.0, 1.0)));
+    }
+
+    #[test]
+    fn many_children_and_a_plane() {
+        let mut children: Vec<Box<dyn Primitive>> = (0 .. 40)
+            .map(|i| sphere(Transformation::translation(Vector3::new(3.0 * i as f64, 0.0, 0.0)), Color::new(1.0, 1.0, 1.0)))
+            .collect();
+        children.push(Box::new(Plane::new(Transformation::translation(Vector3::new(0.0, -2.0, 0.0)), Material::from_color(Color::new(1.0, 1.0, 1.0)))));
+        let group = Group::new(Transformation::identity(), children);
+        let r = Ray::new(Point3::new(60.0, 5.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        let xs = group.intersect(&r);
+        assert_eq!(xs.iter().map(|i| (i.dist, i.handle.path[0])).collect::<Vec<(f64, usize)>>(), vec![(4.0, 20), (6.0, 20), (7.0, 40)]);
+    }
+
+    #[test]
+    fn empty_group_is_never_hit() {
+        let group = Group::new(Transformation::identity(), vec![]);
+        assert!(group.intersect(&Ray::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0))).is_empty());
+        // asking anyway does not panic
+        assert_eq!(group.material().color.r, 1.0);
+        assert_eq!(group.surface_normal(&Point3::origin()).y, 1.0);
+    }
+
+}
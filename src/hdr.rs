@@ -0,0 +1,225 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use crate::image::*;
+
+// shared exponent encoding of the radiance format, the mantissas of all
+// three channels are scaled by the exponent of the brightest one
+pub fn rgbe(c: &Color) -> [u8; 4] {
+    let v = c.r.max(c.g).max(c.b);
+    if v < 1e-32 {
+        [0, 0, 0, 0]
+    } else {
+        let e = v.log2().floor() as i32 + 1;
+        let scale = 256.0 / f64::powi(2.0, e);
+        let byte = |x: f64| (x.max(0.0) * scale).min(255.0) as u8;
+        [byte(c.r), byte(c.g), byte(c.b), (e + 128) as u8]
+    }
+}
+
+pub fn from_rgbe(p: [u8; 4]) -> Color {
+    if p[3] == 0 {
+        Color::black()
+    } else {
+        // the writer truncates, so the middle of the bucket is the best guess
+        let f = f64::powi(2.0, p[3] as i32 - 136);
+        Color::new((p[0] as f64 + 0.5) * f, (p[1] as f64 + 0.5) * f, (p[2] as f64 + 0.5) * f)
+    }
+}
+
+// one component of a scanline as runs (128 + n, value) and literals (n, values...)
+fn rle(bytes: &[u8], out: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < bytes.len() {
+        let mut run = 1;
+        while i + run < bytes.len() && run < 127 && bytes[i + run] == bytes[i] {
+            run += 1;
+        }
+        if run >= 3 {
+            out.push(128 + run as u8);
+            out.push(bytes[i]);
+            i += run;
+        } else {
+            let start = i;
+            while i < bytes.len() && i - start < 128 {
+                if i + 2 < bytes.len() && bytes[i] == bytes[i + 1] && bytes[i] == bytes[i + 2] {
+                    break;
+                }
+                i += 1;
+            }
+            out.push((i - start) as u8);
+            out.extend_from_slice(&bytes[start .. i]);
+        }
+    }
+}
+
+pub fn write_hdr(img: &Image, file: &str) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(file)?);
+    write!(w, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", img.h, img.w)?;
+    // the run length encoding only exists for widths in 8 .. 32767
+    let encode = img.w >= 8 && img.w < 0x8000;
+    for y in 0 .. img.h {
+        let line: Vec<[u8; 4]> = (0 .. img.w).map(|x| rgbe(img.pixel(x, y))).collect();
+        if encode {
+            let mut out = vec![2, 2, (img.w >> 8) as u8, (img.w & 0xff) as u8];
+            for c in 0 .. 4 {
+                let component: Vec<u8> = line.iter().map(|p| p[c]).collect();
+                rle(&component, &mut out);
+            }
+            w.write_all(&out)?;
+        } else {
+            for p in line.iter() {
+                w.write_all(p)?;
+            }
+        }
+    }
+    w.flush()
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_line<R: BufRead>(r: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    if r.read_line(&mut line)? == 0 {
+        return Err(invalid("truncated radiance header"));
+    }
+    Ok(line.trim_end().to_string())
+}
+
+fn read_byte<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut b = [0];
+    r.read_exact(&mut b)?;
+    Ok(b[0])
+}
+
+// flat pixels and new style run length encoded scanlines in the standard
+// -Y h +X w orientation, old style runs are not supported
+pub fn read_hdr(file: &str) -> io::Result<Image> {
+    let mut r = BufReader::new(File::open(file)?);
+    let magic = read_line(&mut r)?;
+    if !magic.starts_with("#?") {
+        return Err(invalid("not a radiance file"));
+    }
+    loop {
+        let line = read_line(&mut r)?;
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+            return Err(invalid("only rgbe radiance files are supported"));
+        }
+    }
+    let resolution = read_line(&mut r)?;
+    let fields: Vec<&str> = resolution.split_whitespace().collect();
+    let (h, w) = match fields.as_slice() {
+        ["-Y", h, "+X", w] => match (h.parse::<usize>(), w.parse::<usize>()) {
+            (Ok(h), Ok(w)) => (h, w),
+            _ => return Err(invalid("bad radiance resolution"))
+        },
+        _ => return Err(invalid("unsupported radiance orientation"))
+    };
+    let mut img = Image::new_rgba(w, h);
+    let mut line = vec![[0u8; 4]; w];
+    for y in 0 .. h {
+        let mut first = [0u8; 4];
+        r.read_exact(&mut first)?;
+        let encoded = (8 .. 0x8000).contains(&w) && first[0] == 2 && first[1] == 2 && first[2] & 0x80 == 0;
+        if encoded {
+            if ((first[2] as usize) << 8 | first[3] as usize) != w {
+                return Err(invalid("radiance scanline width mismatch"));
+            }
+            for c in 0 .. 4 {
+                let mut x = 0;
+                while x < w {
+                    let n = read_byte(&mut r)? as usize;
+                    if n > 128 {
+                        let run = n - 128;
+                        let v = read_byte(&mut r)?;
+                        if x + run > w {
+                            return Err(invalid("radiance run overflows the scanline"));
+                        }
+                        for p in line[x .. x + run].iter_mut() {
+                            p[c] = v;
+                        }
+                        x += run;
+                    } else {
+                        if n == 0 || x + n > w {
+                            return Err(invalid("bad radiance literal run"));
+                        }
+                        for p in line[x .. x + n].iter_mut() {
+                            p[c] = read_byte(&mut r)?;
+                        }
+                        x += n;
+                    }
+                }
+            }
+        } else {
+            line[0] = first;
+            for p in line.iter_mut().skip(1) {
+                r.read_exact(p)?;
+            }
+        }
+        for (x, p) in line.iter().enumerate() {
+            img.set_rgb(x, y, &from_rgbe(*p));
+        }
+    }
+    Ok(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(img: &Image) -> Image {
+        let file = std::env::temp_dir().join(format!("raytracer_hdr_test_{}_{}.hdr", img.w, std::process::id()));
+        let file = file.to_str().unwrap();
+        write_hdr(img, file).unwrap();
+        let read = read_hdr(file).unwrap();
+        std::fs::remove_file(file).unwrap();
+        read
+    }
+
+    // what survives the shared exponent, exactly
+    fn assert_stored(img: &Image, read: &Image) {
+        assert_eq!((read.w, read.h), (img.w, img.h));
+        for (x, y, c) in img.pixels() {
+            let expected = from_rgbe(rgbe(c));
+            let got = read.pixel(x, y);
+            assert_eq!((got.r, got.g, got.b), (expected.r, expected.g, expected.b), "{} {}", x, y);
+        }
+    }
+
+    #[test]
+    fn shared_exponent() {
+        assert_eq!(rgbe(&Color::new(1.0, 0.5, 0.0)), [128, 64, 0, 129]);
+        assert_eq!(rgbe(&Color::black()), [0, 0, 0, 0]);
+        let c = from_rgbe(rgbe(&Color::new(1000.0, 3.0, 0.001)));
+        assert!((c.r - 1000.0).abs() / 1000.0 < 1.0 / 128.0);
+    }
+
+    #[test]
+    fn run_length_encoded_round_trip() {
+        // a run far longer than 127, literals and short runs mixed
+        let mut img = Image::new_rgba(300, 3);
+        for (x, y, c) in img.pixels_mut() {
+            *c = match y {
+                0 => Color::new(2.5, 0.25, 0.0),
+                1 => Color::new(x as f64 / 7.0, (x % 3) as f64, 100.0 / (x + 1) as f64),
+                _ => Color::new((x / 4) as f64, 1.0, if x % 200 < 150 { 0.5 } else { x as f64 })
+            };
+        }
+        assert_stored(&img, &round_trip(&img));
+    }
+
+    #[test]
+    fn wide_scanlines_are_flat() {
+        let mut img = Image::new_rgba(40000, 1);
+        for (x, _, c) in img.pixels_mut() {
+            *c = Color::new((x % 256) as f64 / 16.0, 1.0, 0.0);
+        }
+        assert_stored(&img, &round_trip(&img));
+    }
+
+}
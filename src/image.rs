@@ -1,20 +1,37 @@
 use std::path::Path;
 use std::fs::File;
-use std::io::BufWriter;
+use std::io;
+use std::io::{BufReader, BufWriter};
+use crate::hdr;
+use crate::ppm;
+use crate::exr;
+use crate::exr::{ExrPixel, ExrCompression};
+use crate::tonemap::ToneMapping;
+use crate::colorspace::Transfer;
 use png::HasParameters;
 
+// linear light with sRGB / Rec.709 primaries, all shading math happens in
+// this space, gamma encoded values only exist in files and are converted
+// with from_srgb on the way in and the tone mapping transfer on the way out
 #[derive(Clone, Debug)]
 pub struct Color {
-    r: f64,
-    g: f64,
-    b: f64,
-    a: f64
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64
 }
 
 impl Color {
     pub fn new(r: f64, g: f64, b: f64) -> Color {
         Color {r, g, b, a: 1.0}
     }
+    pub fn from_srgb(r: f64, g: f64, b: f64) -> Color {
+        let t = Transfer::Srgb;
+        Color::new(t.decode(r), t.decode(g), t.decode(b))
+    }
+    pub fn from_srgb8(r: u8, g: u8, b: u8) -> Color {
+        Color::from_srgb(r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0)
+    }
     pub fn black() -> Color {
         Color {r: 0.0, g: 0.0, b: 0.0, a: 1.0}
     }
@@ -32,65 +49,472 @@ impl Color {
     }
 }
 
+// pixels are addressed as (x, y) from the top left corner, stored row by row
+#[derive(Clone)]
 pub struct Image {
     img: Vec<Color>,
     pub w: usize,
-    pub h: usize
+    pub h: usize,
+    // applied whenever the float colors are quantized for 8 or 16 bit output
+    pub tone_mapping: ToneMapping
 }
 
 impl Image {
 
     pub fn new_rgba(w: usize, h: usize) -> Image {
         let img = vec![Color::black(); w * h];
-        Image {img, w, h}
+        Image {img, w, h, tone_mapping: ToneMapping::default()}
+    }
+
+    pub fn set_rgb(&mut self, x: usize, y: usize, c: &Color) {
+        let w = self.w;
+        self.img[y * w + x] = c.clone();
+    }
+
+    // turns gamma encoded content, such as a loaded texture, into linear light
+    pub fn linearize(&mut self, transfer: Transfer) {
+        for c in self.img.iter_mut() {
+            c.r = transfer.decode(c.r);
+            c.g = transfer.decode(c.g);
+            c.b = transfer.decode(c.b);
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> &Color {
+        &self.img[y * self.w + x]
+    }
+
+    pub fn pixel_mut(&mut self, x: usize, y: usize) -> &mut Color {
+        let w = self.w;
+        &mut self.img[y * w + x]
+    }
+
+    pub fn at(&self, x: usize, y: usize) -> Color {
+        self.pixel(x, y).clone()
+    }
+
+    // (x, y, color) in storage order
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize, &Color)> {
+        let w = self.w;
+        self.img.iter().enumerate().map(move |(i, c)| (i % w, i / w, c))
+    }
+
+    pub fn pixels_mut(&mut self) -> impl Iterator<Item = (usize, usize, &mut Color)> {
+        let w = self.w;
+        self.img.iter_mut().enumerate().map(move |(i, c)| (i % w, i / w, c))
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[Color]> {
+        self.img.chunks(self.w)
+    }
+
+    // bilinear lookup at continuous coordinates where pixel centers sit at
+    // x + 0.5, positions outside are clamped to the border, an empty image
+    // has nothing to sample
+    pub fn sample(&self, fx: f64, fy: f64) -> Option<Color> {
+        if self.w == 0 || self.h == 0 {
+            return None;
+        }
+        Some(self.bilinear(fx, fy))
+    }
+
+    fn bilinear(&self, fx: f64, fy: f64) -> Color {
+        let fx = (fx - 0.5).max(0.0).min((self.w - 1) as f64);
+        let fy = (fy - 0.5).max(0.0).min((self.h - 1) as f64);
+        let (x0, y0) = (fx.floor() as usize, fy.floor() as usize);
+        let (x1, y1) = (usize::min(x0 + 1, self.w - 1), usize::min(y0 + 1, self.h - 1));
+        let (tx, ty) = (fx - x0 as f64, fy - y0 as f64);
+        let lerp = |a: &Color, b: &Color, t: f64| {
+            let mut c = a.scale(1.0 - t).add(&b.scale(t));
+            c.a = a.a * (1.0 - t) + b.a * t;
+            c
+        };
+        let top = lerp(self.pixel(x0, y0), self.pixel(x1, y0), tx);
+        let bottom = lerp(self.pixel(x0, y1), self.pixel(x1, y1), tx);
+        lerp(&top, &bottom, ty)
+    }
+
+    pub fn crop(&self, x: usize, y: usize, w: usize, h: usize) -> Image {
+        assert!(x + w <= self.w && y + h <= self.h, "crop outside of the image");
+        let mut cropped = Image::new_rgba(w, h);
+        cropped.tone_mapping = self.tone_mapping.clone();
+        for (cx, cy, c) in cropped.pixels_mut() {
+            *c = self.at(x + cx, y + cy);
+        }
+        cropped
+    }
+
+    // copies another image in with its top left corner at (x, y)
+    pub fn paste(&mut self, x: usize, y: usize, other: &Image) {
+        assert!(x + other.w <= self.w && y + other.h <= self.h, "paste outside of the image");
+        for (ox, oy, c) in other.pixels() {
+            *self.pixel_mut(x + ox, y + oy) = c.clone();
+        }
     }
 
-    pub fn set_rgb(&mut self, i: usize, j: usize, c: &Color) {
-        self.img[i * self.h + j] = c.clone();
+    // bilinear, when shrinking by more than half every output pixel averages
+    // a box of samples so thin features do not alias away
+    pub fn resize(&self, w: usize, h: usize) -> io::Result<Image> {
+        if self.w == 0 || self.h == 0 || w == 0 || h == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot resize from or to an empty image"));
+        }
+        let mut resized = Image::new_rgba(w, h);
+        resized.tone_mapping = self.tone_mapping.clone();
+        let (sx, sy) = (self.w as f64 / w as f64, self.h as f64 / h as f64);
+        let (nx, ny) = (sx.ceil().max(1.0) as usize, sy.ceil().max(1.0) as usize);
+        for (x, y, c) in resized.pixels_mut() {
+            let mut sum = Color::black();
+            let mut alpha = 0.0;
+            for j in 0 .. ny {
+                for i in 0 .. nx {
+                    let fx = (x as f64 + (i as f64 + 0.5) / nx as f64) * sx;
+                    let fy = (y as f64 + (j as f64 + 0.5) / ny as f64) * sy;
+                    let s = self.bilinear(fx, fy);
+                    sum = sum.add(&s);
+                    alpha += s.a;
+                }
+            }
+            let n = (nx * ny) as f64;
+            *c = sum.scale(1.0 / n);
+            c.a = alpha / n;
+        }
+        Ok(resized)
+    }
+
+    pub fn flip_horizontal(&mut self) {
+        for row in self.img.chunks_mut(self.w) {
+            row.reverse();
+        }
     }
 
-    pub fn at(&self, i: usize, j: usize) -> Color {
-        self.img[i * self.w + j].clone()
+    pub fn flip_vertical(&mut self) {
+        for y in 0 .. self.h / 2 {
+            let (top, bottom) = self.img.split_at_mut((self.h - 1 - y) * self.w);
+            top[y * self.w .. (y + 1) * self.w].swap_with_slice(&mut bottom[.. self.w]);
+        }
     }
 
     fn clip2byte(x: f64) -> u8 {
         if x > 255.0 {
             255
+        } else if x < 0.0 {
+            0
         } else {
-            if x < 0.0 {
-                0
-            } else {
-                x as u8
-            }
+            x.round() as u8
         }
     }
 
+    fn clip2short(x: f64) -> u16 {
+        if x > 65535.0 {
+            65535
+        } else if x < 0.0 {
+            0
+        } else {
+            x.round() as u16
+        }
+    }
+
+    pub fn rgb8(&self, c: &Color) -> [u8; 4] {
+        let c = self.tone_mapping.apply(c);
+        [
+            Image::clip2byte(c.r * 255.0),
+            Image::clip2byte(c.g * 255.0),
+            Image::clip2byte(c.b * 255.0),
+            Image::clip2byte(c.a * 255.0)
+        ]
+    }
+
+    pub fn rgb16(&self, c: &Color) -> [u16; 4] {
+        let c = self.tone_mapping.apply(c);
+        [
+            Image::clip2short(c.r * 65535.0),
+            Image::clip2short(c.g * 65535.0),
+            Image::clip2short(c.b * 65535.0),
+            Image::clip2short(c.a * 65535.0)
+        ]
+    }
+
     pub fn flat(&self) -> Vec<u8> {
         let mut bytes = vec![];
-        for i in 0 .. self.h {
-            for j in 0 .. self.w {
-                let c = self.at(i, j);
-                let ur = Image::clip2byte(c.r * 255.0);
-                let ug = Image::clip2byte(c.g * 255.0);
-                let ub = Image::clip2byte(c.b * 255.0);
-                let ua = Image::clip2byte(c.a * 255.0);
-                bytes.push(ur);
-                bytes.push(ug);
-                bytes.push(ub);
-                bytes.push(ua);
+        for c in self.img.iter() {
+            bytes.extend_from_slice(&self.rgb8(c));
+        }
+        bytes
+    }
+
+    pub fn flat16(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        for c in self.img.iter() {
+            for v in self.rgb16(c).iter() {
+                bytes.extend_from_slice(&v.to_be_bytes());
             }
         }
         bytes
     }
 
-    pub fn write_ppm(&self, file: String) {
-        let path = Path::new(&file);
-        let file = File::create(path).unwrap();
-        let ref mut w = BufWriter::new(file);
-        let mut encoder = png::Encoder::new(w, self.h as u32, self.w as u32);  
-        encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
-        let mut writer = encoder.write_header().unwrap();
-        writer.write_image_data(&self.flat()).unwrap();
+    fn encode_png(&self, file: &str, depth: png::BitDepth, data: &[u8]) -> io::Result<()> {
+        let path = Path::new(file);
+        let w = &mut BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(w, self.w as u32, self.h as u32);
+        encoder.set(png::ColorType::RGBA).set(depth);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(data)?;
+        Ok(())
+    }
+
+    pub fn write_png(&self, file: &str) -> io::Result<()> {
+        self.encode_png(file, png::BitDepth::Eight, &self.flat())
+    }
+
+    pub fn write_png16(&self, file: &str) -> io::Result<()> {
+        self.encode_png(file, png::BitDepth::Sixteen, &self.flat16())
+    }
+
+    pub fn write_ppm(&self, file: &str) -> io::Result<()> {
+        ppm::write_ppm(self, file, true)
+    }
+
+    pub fn write_ppm_plain(&self, file: &str) -> io::Result<()> {
+        ppm::write_ppm(self, file, false)
+    }
+
+    pub fn write_pfm(&self, file: &str) -> io::Result<()> {
+        ppm::write_pfm(self, file)
+    }
+
+    pub fn write_hdr(&self, file: &str) -> io::Result<()> {
+        hdr::write_hdr(self, file)
+    }
+
+    pub fn write_exr(&self, file: &str, pixel_type: ExrPixel, compression: ExrCompression) -> io::Result<()> {
+        exr::write_exr(self, file, pixel_type, compression)
+    }
+
+    // 8 and 16 bit pngs are taken to be sRGB encoded and come back as linear
+    // light, grayscale is spread over the three channels
+    pub fn read_png(file: &str) -> io::Result<Image> {
+        let mut decoder = png::Decoder::new(BufReader::new(File::open(file)?));
+        decoder.set(png::Transformations::EXPAND);
+        let (info, mut reader) = decoder.read_info()?;
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data)?;
+        let channels = info.color_type.samples();
+        // the decoder reports the depth of the file as 8 bits even for 16
+        // bit files, the line size tells the truth
+        let (bytes, max) = if info.line_size >= info.width as usize * channels * 2 {
+            (2, 65535.0)
+        } else {
+            (1, 255.0)
+        };
+        let value = |i: usize| if bytes == 2 {
+            u16::from_be_bytes([data[i * 2], data[i * 2 + 1]]) as f64 / max
+        } else {
+            data[i] as f64 / max
+        };
+        let mut img = Image::new_rgba(info.width as usize, info.height as usize);
+        for (i, c) in img.img.iter_mut().enumerate() {
+            let s = i * channels;
+            *c = match channels {
+                1 => Color::from_srgb(value(s), value(s), value(s)),
+                2 => Color {a: value(s + 1), ..Color::from_srgb(value(s), value(s), value(s))},
+                3 => Color::from_srgb(value(s), value(s + 1), value(s + 2)),
+                _ => Color {a: value(s + 3), ..Color::from_srgb(value(s), value(s + 1), value(s + 2))}
+            };
+        }
+        Ok(img)
+    }
+
+    pub fn read_hdr(file: &str) -> io::Result<Image> {
+        hdr::read_hdr(file)
+    }
+
+    // picks the decoder from the file extension
+    pub fn read(file: &str) -> io::Result<Image> {
+        match ImageFormat::from_path(file) {
+            Some(ImageFormat::Png) | Some(ImageFormat::Png16) => Image::read_png(file),
+            Some(ImageFormat::Hdr) => Image::read_hdr(file),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("cannot read image format: {}", file)))
+        }
+    }
+
+    pub fn write_as(&self, file: &str, format: ImageFormat) -> io::Result<()> {
+        match format {
+            ImageFormat::Png => self.write_png(file),
+            ImageFormat::Png16 => self.write_png16(file),
+            ImageFormat::Ppm => self.write_ppm(file),
+            ImageFormat::PpmPlain => self.write_ppm_plain(file),
+            ImageFormat::Pfm => self.write_pfm(file),
+            ImageFormat::Hdr => self.write_hdr(file),
+            ImageFormat::Exr(pixel_type, compression) => self.write_exr(file, pixel_type, compression)
+        }
+    }
+
+    // picks the encoder from the file extension
+    pub fn write(&self, file: &str) -> io::Result<()> {
+        match ImageFormat::from_path(file) {
+            Some(format) => self.write_as(file, format),
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown image format: {}", file)))
+        }
+    }
+
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Png16,
+    Ppm,
+    PpmPlain,
+    Pfm,
+    Hdr,
+    Exr(ExrPixel, ExrCompression)
+}
+
+impl ImageFormat {
+
+    // a second extension picks a variant, "scene.16.png" is written with 16
+    // bits per channel and "scene.p3.ppm" as plain text
+    pub fn from_path(file: &str) -> Option<ImageFormat> {
+        let path = Path::new(file);
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        let variant = Path::new(path.file_stem()?).extension()
+            .and_then(|v| v.to_str())
+            .map(|v| v.to_lowercase());
+        match (ext.as_str(), variant.as_deref()) {
+            ("png", Some("16")) => Some(ImageFormat::Png16),
+            ("ppm", Some("p3")) | ("pnm", Some("p3")) => Some(ImageFormat::PpmPlain),
+            (ext, _) => ImageFormat::from_extension(ext)
+        }
+    }
+
+    // stores values outside [0, 1] as they are
+    pub fn is_float(&self) -> bool {
+        matches!(*self, ImageFormat::Pfm | ImageFormat::Hdr | ImageFormat::Exr(..))
+    }
+
+    fn from_extension(ext: &str) -> Option<ImageFormat> {
+        match ext {
+            "png" => Some(ImageFormat::Png),
+            "ppm" | "pnm" => Some(ImageFormat::Ppm),
+            "pfm" => Some(ImageFormat::Pfm),
+            "hdr" | "pic" => Some(ImageFormat::Hdr),
+            "exr" => Some(ImageFormat::Exr(ExrPixel::Half, ExrCompression::Zip)),
+            _ => None
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tonemap::ToneMap;
+
+    // black, white
+    fn pair() -> Image {
+        let mut img = Image::new_rgba(2, 1);
+        img.set_rgb(1, 0, &Color::new(1.0, 1.0, 1.0));
+        img
+    }
+
+    #[test]
+    fn bilinear_sampling_clamps_at_the_edges() {
+        let img = pair();
+        assert_eq!(img.sample(0.5, 0.5).unwrap().r, 0.0);
+        assert_eq!(img.sample(1.0, 0.5).unwrap().r, 0.5);
+        assert_eq!(img.sample(1.25, 0.5).unwrap().r, 0.75);
+        assert_eq!(img.sample(-3.0, -3.0).unwrap().r, 0.0);
+        assert_eq!(img.sample(7.0, 9.0).unwrap().r, 1.0);
+        assert!(Image::new_rgba(0, 4).sample(0.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn resizing() {
+        let img = pair();
+        let wide = img.resize(4, 1).unwrap();
+        let row: Vec<f64> = (0 .. 4).map(|x| wide.at(x, 0).r).collect();
+        assert_eq!(row, vec![0.0, 0.25, 0.75, 1.0]);
+        // shrinking averages both pixels
+        assert_eq!(img.resize(1, 1).unwrap().at(0, 0).r, 0.5);
+        assert!(img.resize(0, 1).is_err());
+        assert!(Image::new_rgba(3, 0).resize(2, 2).is_err());
+    }
+
+    #[test]
+    fn crop_paste_and_flip() {
+        let mut img = Image::new_rgba(4, 3);
+        for (x, y, c) in img.pixels_mut() {
+            *c = Color::new(x as f64, y as f64, 0.0);
+        }
+        let corner = img.crop(2, 1, 2, 2);
+        assert_eq!((corner.w, corner.h), (2, 2));
+        assert_eq!((corner.at(1, 1).r, corner.at(1, 1).g), (3.0, 2.0));
+        let mut blank = Image::new_rgba(4, 3);
+        blank.paste(2, 1, &corner);
+        assert_eq!(blank.at(3, 2).r, 3.0);
+        assert_eq!(blank.at(1, 1).r, 0.0);
+        img.flip_vertical();
+        assert_eq!(img.at(1, 0).g, 2.0);
+        assert_eq!(img.at(1, 1).g, 1.0);
+        img.flip_horizontal();
+        assert_eq!(img.at(0, 0).r, 3.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "crop outside")]
+    fn crop_past_the_edge() {
+        Image::new_rgba(4, 3).crop(3, 0, 2, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "paste outside")]
+    fn paste_past_the_edge() {
+        Image::new_rgba(4, 3).paste(0, 2, &Image::new_rgba(1, 2));
+    }
+
+    #[test]
+    fn encoding_rounds_to_the_nearest_code() {
+        let mut img = Image::new_rgba(1, 1);
+        img.tone_mapping = ToneMapping::new(0.0, ToneMap::Clip, Transfer::Linear);
+        let mut c = Color::new(0.5, 0.999, 0.3 / 255.0);
+        c.a = 0.0;
+        assert_eq!(img.rgb8(&c), [128, 255, 0, 0]);
+        assert_eq!(img.rgb16(&c), [32768, 65469, 77, 0]);
+        assert_eq!(img.rgb8(&Color::new(2.0, -1.0, 0.7 / 255.0)), [255, 0, 1, 255]);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn png_round_trip() {
+        let mut img = Image::new_rgba(16, 4);
+        for (x, y, c) in img.pixels_mut() {
+            *c = Color::new(x as f64 / 15.0, y as f64 / 3.0, 0.2);
+            c.a = 1.0;
+        }
+        for (name, tolerance) in [("8.png", 0.01), ("16.png", 1e-4)].iter() {
+            let file = std::env::temp_dir().join(format!("raytracer_png_test_{}.{}", std::process::id(), name));
+            let file = file.to_str().unwrap();
+            img.write(file).unwrap();
+            let read = Image::read(file).unwrap();
+            std::fs::remove_file(file).unwrap();
+            assert_eq!((read.w, read.h), (16, 4));
+            for (x, y, c) in img.pixels() {
+                let r = read.pixel(x, y);
+                for (a, b) in [(c.r, r.r), (c.g, r.g), (c.b, r.b), (c.a, r.a)].iter() {
+                    assert!((a - b).abs() < *tolerance, "{} {} {} {}", name, x, a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(ImageFormat::from_path("scene.png"), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path("out/scene.16.PNG"), Some(ImageFormat::Png16));
+        assert_eq!(ImageFormat::from_path("frame_0001.png"), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path("scene.p3.ppm"), Some(ImageFormat::PpmPlain));
+        assert_eq!(ImageFormat::from_path("scene.16.ppm"), Some(ImageFormat::Ppm));
+        assert_eq!(ImageFormat::from_path("scene.exr"), Some(ImageFormat::Exr(ExrPixel::Half, ExrCompression::Zip)));
+        assert_eq!(ImageFormat::from_path("scene"), None);
+    }
+
+}
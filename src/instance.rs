@@ -0,0 +1,115 @@
+use std::sync::Arc;
+use crate::tuples::*;
+use crate::transformations::*;
+use crate::geometry::*;
+use crate::material::Material;
+use crate::bounds::Bounds;
+
+// a shape or whole group shared between many placements, each instance only
+// costs a transformation so a forest of one tree stays one tree in memory
+pub struct Instance {
+    transformation: Transformation,
+    inverse: Transformation,
+    shared: Arc<dyn Primitive>,
+    // of the shared shape and placed, the shared shape never changes
+    shared_bounds: Bounds,
+    bounds: Bounds,
+    // replaces the materials of everything in the shared shape
+    material: Option<Material>
+}
+
+impl Instance {
+
+    pub fn new(transformation: Transformation, shared: Arc<dyn Primitive>) -> Instance {
+        let inverse = transformation.inverse().unwrap();
+        let shared_bounds = shared.bounds();
+        let bounds = shared_bounds.transform(&transformation);
+        Instance{transformation, inverse, shared, shared_bounds, bounds, material: None}
+    }
+
+    pub fn with_material(self, material: Material) -> Instance {
+        Instance {material: Some(material), ..self}
+    }
+
+}
+
+impl Primitive for Instance {
+
+    fn intersect(&self, ray: &Ray) -> Vec<Intersection> {
+        let local = ray.transform(&self.inverse);
+        if !self.shared_bounds.hit(&local) {
+            return vec![];
+        }
+        self.shared.intersect(&local)
+    }
+
+    fn surface_normal(&self, p: &Point3) -> Normal3 {
+        self.normal_along(p, &[], 0.0)
+    }
+
+    fn normal_along(&self, p: &Point3, path: &[usize], time: f64) -> Normal3 {
+        let object_normal = self.shared.normal_along(&self.inverse.transform(p), path, time);
+        self.transformation.transform(&object_normal).normalized()
+    }
+
+    fn material(&self) -> Material {
+        self.material_along(&[])
+    }
+
+    fn material_along(&self, path: &[usize]) -> Material {
+        match &self.material {
+            Some(material) => material.clone(),
+            None => self.shared.material_along(path)
+        }
+    }
+
+    fn starts_inside(&self, ray: &Ray) -> bool {
+        self.shared.starts_inside(&ray.transform(&self.inverse))
+    }
+
+    fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Color;
+    use crate::group::Group;
+
+    fn tree() -> Arc<dyn Primitive> {
+        let material = Material::from_color(Color::new(0.1, 0.6, 0.1));
+        Arc::new(Group::new(Transformation::identity(), vec![
+            Box::new(UnitSphere::new(Transformation::translation(Vector3::new(0.0, 2.0, 0.0)), material))
+        ]))
+    }
+
+    #[test]
+    fn instances_share_one_tree() {
+        let tree = tree();
+        let forest: Vec<Instance> = (0 .. 100)
+            .map(|i| Instance::new(Transformation::translation(Vector3::new(3.0 * i as f64, 0.0, 0.0)), tree.clone()))
+            .collect();
+        assert_eq!(Arc::strong_count(&tree), 101);
+        let r = Ray::new(Point3::new(30.0, 2.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        let hits: Vec<usize> = forest.iter().enumerate().filter(|(_, t)| !t.intersect(&r).is_empty()).map(|(i, _)| i).collect();
+        assert_eq!(hits, vec![10]);
+    }
+
+    #[test]
+    fn transformed_normal_and_material_override() {
+        let autumn = Material::from_color(Color::new(0.8, 0.4, 0.1));
+        let big = Instance::new(Transformation::scale(Vector3::new(2.0, 2.0, 2.0)), tree()).with_material(autumn);
+        let r = Ray::new(Point3::new(0.0, 4.0, -10.0), Vector3::new(0.0, 0.0, 1.0));
+        let xs = big.intersect(&r);
+        assert_eq!(xs.iter().map(|i| i.dist).collect::<Vec<f64>>(), vec![8.0, 12.0]);
+        let n = big.normal_along(&r.position(xs[0].dist), &xs[0].handle.path, 0.0);
+        assert!((n.z + 1.0).abs() < 1e-9);
+        assert_eq!(big.material_along(&xs[0].handle.path).color.r, 0.8);
+        let plain = Instance::new(Transformation::identity(), tree());
+        assert_eq!(plain.material().color.g, 0.6);
+    }
+
+}
@@ -5,38 +5,51 @@ use crate::geometry::*;
 
 pub struct PointLight {
     pub color: Color,
-    pub pos: Tuple4D
+    pub pos: Point3
 }
 
 impl PointLight {
 
-    pub fn new(color: Color, pos: Tuple4D) -> PointLight {
+    pub fn new(color: Color, pos: Point3) -> PointLight {
         PointLight {color, pos}
     }
 
-    pub fn lighting(&self, material: &Material, point: &Tuple4D, eye: &Tuple4D, normal: &Tuple4D, in_shadow: bool) -> Color {
+    pub fn lighting(&self, material: &Material, point: &Point3, eye: &Vector3, normal: &Normal3, in_shadow: bool) -> Color {
+        self.shade(material, point, eye, normal, in_shadow).total()
+    }
+
+    pub fn shade(&self, material: &Material, point: &Point3, eye: &Vector3, normal: &Normal3, in_shadow: bool) -> Shading {
         let effective_color = material.color.mul(&self.color);
-        let light_dir = self.pos.sub(&point).normalized();   
+        let light_dir = (self.pos - *point).normalized();
         let ambient = effective_color.scale(material.ambient);
-        let light2normal = light_dir.dot(&normal);
+        let light2normal = normal.dot(&light_dir);
         if light2normal < 0.0 || in_shadow {
-            let total = ambient;
-            total            
+            Shading {ambient, diffuse: Color::black(), specular: Color::black()}
         } else {
             let diffuse = effective_color.scale(material.diffuse).scale(light2normal);
-            let reflection_vec = reflect(&light_dir.scale(-1.0), &normal);
-            let reflection = reflection_vec.dot(&eye);
+            let reflection_vec = reflect(&-light_dir, normal);
+            let reflection = reflection_vec.dot(eye);
             if reflection <= 0.0 {
-                let total = ambient.add(&diffuse);
-                total
+                Shading {ambient, diffuse, specular: Color::black()}
             } else {
                 let factor = f64::powf(reflection, material.shininess);
                 let specular = self.color.scale(material.specular).scale(factor);
-                let total = ambient.add(&diffuse).add(&specular);
-                total
+                Shading {ambient, diffuse, specular}
             }
         }
-
     }
 
-}
\ No newline at end of file
+}
+
+// the phong terms of a single light kept apart for the render passes
+pub struct Shading {
+    pub ambient: Color,
+    pub diffuse: Color,
+    pub specular: Color
+}
+
+impl Shading {
+    pub fn total(&self) -> Color {
+        self.ambient.add(&self.diffuse).add(&self.specular)
+    }
+}
@@ -2,90 +2,251 @@ pub mod tuples;
 pub mod matrices;
 pub mod transformations;
 pub mod geometry;
+pub mod csg;
+pub mod bounds;
+pub mod bvh;
+pub mod group;
+pub mod instance;
 pub mod image;
 pub mod raytracer;
 pub mod material;
 pub mod light;
+pub mod camera;
+pub mod quaternion;
+pub mod random;
+pub mod animation;
+pub mod hdr;
+pub mod exr;
+pub mod ppm;
+pub mod tonemap;
+pub mod colorspace;
+pub mod diff;
+pub mod scenes;
+pub mod aov;
+pub mod denoise;
+pub mod progressive;
+pub mod checkpoint;
+pub mod stats;
+pub mod distributed;
+#[cfg(test)]
+mod golden;
 
+use std::env;
 use image::*;
-use transformations::*;
-use tuples::*;
-use geometry::*;
-use raytracer::*;
-use light::*;
-use material::*;
+use tonemap::*;
+use colorspace::*;
+use diff::*;
+use scenes::*;
+use aov::*;
+use raytracer::RenderSettings;
+use denoise::Denoiser;
+use progressive::*;
+use stats::ConsoleObserver;
+use animation::frame_path;
+use distributed::*;
 
-fn main() {    
+fn usage() -> ! {
+    eprintln!("usage: raytracer [--scene NAME] [--frames FIRST LAST] [--output FILE] [--exposure STOPS] [--tonemap OPERATOR] [--transfer CURVE]");
+    eprintln!("                 [--passes LIST] [--samples N] [--denoise FILTER]");
+    eprintln!("                 [--max-passes N] [--time SECONDS] [--noise TARGET]");
+    eprintln!("                 [--snapshot FILE] [--snapshot-every PASSES] [--snapshot-seconds SECONDS]");
+    eprintln!("                 [--checkpoint FILE] [--checkpoint-every PASSES] [--checkpoint-seconds SECONDS] [--resume]");
+    eprintln!("                 [--workers ADDRESS,...] [--tile SIZE]");
+    eprintln!("       NAME is {} (default room),", NAMES.join(", "));
+    eprintln!("       a run of '#' in FILE is replaced by the frame number,");
+    eprintln!("       the extension picks the format: png, ppm, pfm, hdr or exr, .16.png writes");
+    eprintln!("       16 bits per channel and .p3.ppm plain text,");
+    eprintln!("       OPERATOR is clip, reinhard, reinhard-extended[:WHITE], aces or hable,");
+    eprintln!("       CURVE is srgb (default), rec709 or linear,");
+    eprintln!("       LIST is all or some of depth,normal,albedo,id,diffuse,specular,reflection,shadow");
+    eprintln!("       which go into layers of an exr or files next to FILE for other formats,");
+    eprintln!("       depth and id need FILE to be pfm, hdr or exr,");
+    eprintln!("       FILTER is atrous[:ITERATIONS] or bilateral[:RADIUS],");
+    eprintln!("       any of --max-passes, --time or --noise renders progressively one sample");
+    eprintln!("       per pixel at a time until the first of them is reached, snapshots and");
+    eprintln!("       checkpoints need one of them and are written every 10 and 60 seconds");
+    eprintln!("       unless told otherwise,");
+    eprintln!("       --resume picks up from the checkpoint and ends with the same image as an");
+    eprintln!("       uninterrupted run,");
+    eprintln!("       --workers splits the beauty image into tiles, or an animation into frames,");
+    eprintln!("       rendered by the workers listening at the addresses, --tile sets the tile");
+    eprintln!("       size of a single frame and cannot go with --frames");
+    eprintln!("       raytracer worker [--listen ADDRESS]");
+    eprintln!("       serves tiles to coordinators, on {} unless told otherwise", DEFAULT_ADDRESS);
+    eprintln!("       raytracer diff A B [--heatmap FILE] [--metric METRIC] [--threshold VALUE]");
+    eprintln!("       METRIC is flip (default), mse, psnr or ssim, exits with 1 when");
+    eprintln!("       the images are further apart than VALUE");
+    std::process::exit(2)
+}
+
+fn diff(mut args: impl Iterator<Item = String>) {
+    let a = args.next().unwrap_or_else(|| usage());
+    let b = args.next().unwrap_or_else(|| usage());
+    let mut heatmap = None;
+    let mut metric = Metric::Flip;
+    let mut threshold = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--heatmap" => heatmap = Some(args.next().unwrap_or_else(|| usage())),
+            "--metric" => metric = args.next().and_then(|a| Metric::parse(&a)).unwrap_or_else(|| usage()),
+            "--threshold" => threshold = Some(args.next().and_then(|a| a.parse::<f64>().ok()).unwrap_or_else(|| usage())),
+            _ => usage()
+        }
+    }
+    let fail = |e: String| -> ! {
+        eprintln!("error: {}", e);
+        std::process::exit(1)
+    };
+    let first = Image::read(&a).unwrap_or_else(|e| fail(e.to_string()));
+    let second = Image::read(&b).unwrap_or_else(|e| fail(e.to_string()));
+    let result = compare(&first, &second).unwrap_or_else(|e| fail(e));
+    println!("mse {:.6}  psnr {:.2} dB  ssim {:.4}  flip {:.4}", result.mse, result.psnr, result.ssim, result.flip);
+    if let Some(file) = heatmap {
+        result.heatmap().write(&file).unwrap_or_else(|e| fail(e.to_string()));
+    }
+    if let Some(threshold) = threshold {
+        if metric.exceeds(&result, threshold) {
+            eprintln!("{:?} {} exceeds the threshold {}", metric, metric.value(&result), threshold);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn worker(mut args: impl Iterator<Item = String>) {
+    let mut address = DEFAULT_ADDRESS.to_string();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => address = args.next().unwrap_or_else(|| usage()),
+            _ => usage()
+        }
+    }
+    let result = std::net::TcpListener::bind(&address).and_then(|listener| {
+        println!("Worker listening on {}", address);
+        serve(listener)
+    });
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    match env::args().nth(1).as_deref() {
+        Some("diff") => return diff(env::args().skip(2)),
+        Some("worker") => return worker(env::args().skip(2)),
+        _ => {}
+    }
     println!("Ray Tracer!");
-    let floor = Plane::new(
-        0, 
-        Transformation::identity(), 
-        Material::new(Color::new(1.0, 0.9, 0.9), 0.1, 0.9, 0.0, 200.0, 0.5)
-    );
-    let left_wall = Plane::new(
-        1,
-        Transformation::chain(&vec![
-            Transformation::translation(Tuple4D::new_vector(0.0, 0.0, 5.0)),
-            Transformation::rotate_y((- std::f64::consts::PI / 4.0).to_degrees()),
-            Transformation::rotate_x((  std::f64::consts::PI / 2.0).to_degrees()),
-        ]),
-        Material::new(Color::new(1.0, 0.9, 0.9), 0.1, 0.9, 0.0, 200.0, 0.5)
-    );
-    let right_wall = Plane::new(
-        2,
-        Transformation::chain(&vec![
-            Transformation::translation(Tuple4D::new_vector(0.0, 0.0, 5.0)),
-            Transformation::rotate_y((std::f64::consts::PI / 4.0).to_degrees()),
-            Transformation::rotate_x((std::f64::consts::PI / 2.0).to_degrees()),
-        ]),
-        Material::new(Color::new(1.0, 0.9, 0.9), 0.1, 0.9, 0.0, 200.0, 0.5)
-    );
-    let middle = UnitSphere::new(
-        3,
-        Transformation::chain(&vec![
-            Transformation::translation(Tuple4D::new_vector(-0.5, 1.0, 0.5))
-        ]),
-        Material::new(Color::new(0.1, 1.0, 0.5), 0.1, 0.7, 0.3, 200.0, 0.0)
-    );
-    let right = UnitSphere::new(
-        4,
-        Transformation::chain(&vec![
-            Transformation::translation(Tuple4D::new_vector(1.5, 0.5, -1.5)),
-            Transformation::scale(Tuple4D::new_vector(0.5, 0.5, 0.5))
-        ]),
-        Material::new(Color::new(0.1, 1.0, 0.5), 0.1, 0.7, 0.3, 200.0, 0.0)
-    );
-    let left = UnitSphere::new(
-        5,
-        Transformation::chain(&vec![
-            Transformation::translation(Tuple4D::new_vector(-1.5, 0.33, -0.75)),
-            Transformation::scale(Tuple4D::new_vector(0.33, 0.33, 0.33))
-        ]),
-        Material::new(Color::new(1.0, 0.8, 0.1), 0.1, 0.7, 0.3, 200.0, 0.0)
-    );
-    let light = PointLight::new(
-        Color::new(1.0, 1.0, 1.0), Tuple4D::new_point(-10.0, 10.0, -10.0));
-    let cam = Camera::new(
-        512.0, 
-        256.0, 
-        (std::f64::consts::PI / 3.0).to_degrees(),
-        Transformation::view(
-            Tuple4D::new_point(0.0, 2.5, -8.0),
-            Tuple4D::new_point(0.0, 1.0, 0.0),
-            Tuple4D::new_vector(0.0, 1.0, 0.0)
-        ) );
-    let world = World::new(
-        vec![
-            Box::from(floor),
-            Box::from(left_wall),
-            Box::from(right_wall),
-            Box::from(middle),
-            Box::from(right),
-            Box::from(left)
-        ], 
-        vec![light]
-    );   
-    let tracer = RayTracer::new(cam, world);
-    let img = tracer.trace(5);
-    img.write_ppm("scene.png".to_string());
+    let mut frames = None;
+    let mut output = None;
+    let mut settings = RenderSettings::default();
+    let mut progressive = Progressive::default();
+    let mut scene = "room".to_string();
+    let mut workers: Vec<String> = vec![];
+    let mut tile_size = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => {
+                let first = args.next().and_then(|a| a.parse::<usize>().ok()).unwrap_or_else(|| usage());
+                let last = args.next().and_then(|a| a.parse::<usize>().ok()).unwrap_or_else(|| usage());
+                frames = Some((first, last));
+            },
+            "--scene" => scene = args.next().filter(|a| NAMES.contains(&a.as_str())).unwrap_or_else(|| usage()),
+            "--workers" => workers = args.next().map(|a| a.split(',').map(String::from).collect()).unwrap_or_else(|| usage()),
+            "--tile" => tile_size = Some(args.next().and_then(|a| a.parse::<usize>().ok()).filter(|&t| t > 0).unwrap_or_else(|| usage())),
+            "--output" => output = Some(args.next().unwrap_or_else(|| usage())),
+            "--exposure" => settings.tone_mapping.exposure = args.next().and_then(|a| a.parse::<f64>().ok()).unwrap_or_else(|| usage()),
+            "--tonemap" => settings.tone_mapping.operator = args.next().and_then(|a| ToneMap::parse(&a)).unwrap_or_else(|| usage()),
+            "--transfer" => settings.tone_mapping.transfer = args.next().and_then(|a| Transfer::parse(&a)).unwrap_or_else(|| usage()),
+            "--passes" => settings.passes = args.next().and_then(|a| Pass::parse_list(&a)).unwrap_or_else(|| usage()),
+            "--samples" => settings.samples = Some(args.next().and_then(|a| a.parse::<usize>().ok()).unwrap_or_else(|| usage())),
+            "--denoise" => settings.denoiser = Some(args.next().and_then(|a| Denoiser::parse(&a)).unwrap_or_else(|| usage())),
+            "--max-passes" => progressive.stop.passes = Some(args.next().and_then(|a| a.parse::<usize>().ok()).unwrap_or_else(|| usage())),
+            "--time" => progressive.stop.seconds = Some(args.next().and_then(|a| a.parse::<f64>().ok()).unwrap_or_else(|| usage())),
+            "--noise" => progressive.stop.noise = Some(args.next().and_then(|a| a.parse::<f64>().ok()).unwrap_or_else(|| usage())),
+            "--snapshot" => progressive.snapshots = Some(Snapshots::new(&args.next().unwrap_or_else(|| usage()))),
+            "--snapshot-every" => {
+                let every = args.next().and_then(|a| a.parse::<usize>().ok()).unwrap_or_else(|| usage());
+                let s = progressive.snapshots.as_mut().unwrap_or_else(|| usage());
+                s.every_passes = Some(every);
+                s.every_seconds = None;
+            },
+            "--snapshot-seconds" => {
+                let every = args.next().and_then(|a| a.parse::<f64>().ok()).unwrap_or_else(|| usage());
+                progressive.snapshots.as_mut().unwrap_or_else(|| usage()).every_seconds = Some(every);
+            },
+            "--checkpoint" => progressive.checkpoints = Some(Checkpoints::new(&args.next().unwrap_or_else(|| usage()))),
+            "--checkpoint-every" => {
+                let every = args.next().and_then(|a| a.parse::<usize>().ok()).unwrap_or_else(|| usage());
+                let c = progressive.checkpoints.as_mut().unwrap_or_else(|| usage());
+                c.every_passes = Some(every);
+                c.every_seconds = None;
+            },
+            "--checkpoint-seconds" => {
+                let every = args.next().and_then(|a| a.parse::<f64>().ok()).unwrap_or_else(|| usage());
+                progressive.checkpoints.as_mut().unwrap_or_else(|| usage()).every_seconds = Some(every);
+            },
+            "--resume" => progressive.resume = true,
+            _ => usage()
+        }
+    }
+    if progressive.stop.is_set() && frames.is_some() {
+        usage();
+    }
+    if progressive.resume && progressive.checkpoints.is_none() {
+        usage();
+    }
+    // a render that never stops progressively would never write them
+    if !progressive.stop.is_set() && (progressive.snapshots.is_some() || progressive.checkpoints.is_some()) {
+        usage();
+    }
+    progressive.scene = scene.clone();
+    // tiles only split a single frame, animations go to workers a frame at a time
+    if tile_size.is_some() && (frames.is_some() || workers.is_empty()) {
+        usage();
+    }
+    // workers only send back the beauty image
+    if !workers.is_empty() && (progressive.stop.is_set() || !settings.render_passes().is_empty()) {
+        usage();
+    }
+    // depth and object ids are clamped away by formats without floats
+    let data = settings.passes.iter().any(|p| matches!(p, Pass::Depth | Pass::ObjectId));
+    if data && !output.as_deref().and_then(ImageFormat::from_path).is_some_and(|f| f.is_float()) {
+        usage();
+    }
+    let (w, h) = (512, 256);
+    let tracer = |frame: usize| frame_by_name(&scene, w as f64, h as f64, frame as f64).unwrap();
+    let result = match frames {
+        Some((first, last)) if !workers.is_empty() => {
+            let pattern = output.unwrap_or_else(|| "frame_####.png".to_string());
+            let frames: Vec<usize> = (first ..= last).collect();
+            Coordinator::new(&workers, &scene, w, h).render(&frames, &settings, &mut ConsoleObserver::default(), |frame, out| {
+                let file = frame_path(&pattern, frame);
+                println!("Frame {} -> {}", frame, file);
+                out.write(&file)
+            })
+        },
+        Some((first, last)) => {
+            let pattern = output.unwrap_or_else(|| "frame_####.png".to_string());
+            (first ..= last).try_for_each(|frame| {
+                let file = frame_path(&pattern, frame);
+                println!("Frame {} -> {}", frame, file);
+                tracer(frame).render_observed(&settings, &mut ConsoleObserver::default()).write(&file)
+            })
+        },
+        None if !workers.is_empty() => {
+            let mut coordinator = Coordinator::new(&workers, &scene, w, h);
+            coordinator.tile_size = tile_size.unwrap_or(coordinator.tile_size);
+            let file = output.unwrap_or_else(|| "scene.png".to_string());
+            coordinator.render(&[0], &settings, &mut ConsoleObserver::default(), |_, out| out.write(&file))
+        },
+        None if progressive.stop.is_set() => render_progressive(&tracer(0), &settings, &progressive, &mut ConsoleObserver::default())
+            .and_then(|out| out.write(&output.unwrap_or_else(|| "scene.png".to_string()))),
+        None => tracer(0).render_observed(&settings, &mut ConsoleObserver::default()).write(&output.unwrap_or_else(|| "scene.png".to_string()))
+    };
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
 }
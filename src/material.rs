@@ -19,3 +19,10 @@ impl Material {
     }        
 
 }
+
+// white with the usual phong constants
+impl Default for Material {
+    fn default() -> Material {
+        Material::from_color(Color::new(1.0, 1.0, 1.0))
+    }
+}
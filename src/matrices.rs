@@ -1,130 +1,192 @@
-use crate::tuples::*;
-
-#[derive(Clone, Debug)]
-pub struct Mat {
-    pub rows: usize,
-    pub cols: usize,
-    x: Vec<f64>
+// row major 4x4 matrix living on the stack
+#[derive(Clone, Copy, Debug)]
+pub struct Mat4 {
+    x: [f64; 16]
 }
 
-impl Mat {
+impl Mat4 {
 
-    pub fn new(x: Vec<f64>, rows: usize) -> Mat {
-        let cols = x.len() / rows;
-        Mat {rows, cols, x}
-    }    
+    pub fn new(x: [f64; 16]) -> Mat4 {
+        Mat4 {x}
+    }
 
-    pub fn eye(n: usize) -> Mat {
-        let mut x = vec![0.0; n * n];
-        let rows = n;
-        let cols = n;
-        for i in 0 .. n {
-            x[i * n + i] = 1.0;
+    pub fn eye() -> Mat4 {
+        let mut x = [0.0; 16];
+        for i in 0 .. 4 {
+            x[i * 4 + i] = 1.0;
         }
-        Mat {rows, cols, x}
-    } 
+        Mat4 {x}
+    }
 
-    pub fn zeros(rows: usize, cols: usize) -> Mat {
-        let x = vec![0.0; rows * cols];
-        Mat {rows, cols, x}
+    pub fn zeros() -> Mat4 {
+        Mat4 {x: [0.0; 16]}
     }
 
     pub fn at(&self, i: usize, j: usize) -> f64 {
-        self.x[i * self.cols + j]
-    }    
-
-    pub fn col(&self, i: usize) -> &[f64] {
-        &self.x[i * self.cols .. (i + 1) * self.cols]
-    }
-
-    pub fn mul(&self, tuple: &Tuple4D) ->  Tuple4D {
-        assert!(self.cols == 4);
-        let x = tuple.col_dot(self.col(0));
-        let y = tuple.col_dot(self.col(1));
-        let z = tuple.col_dot(self.col(2));
-        let w = tuple.col_dot(self.col(3));
-        Tuple4D::new(x, y, z, w)
-    }
-    
-    pub fn mat_mul(&self, other: &Mat) -> Mat {
-        assert!(self.cols == other.rows);
-        let mut result = Mat::zeros(self.rows, other.cols);
-        for i in 0 .. self.rows {
-            for j in 0 .. other.cols {                
-                for k in 0 .. self.cols {
-                    result.x[i * other.cols + j] += self.at(i, k) * other.at(k, j);
-                }
-            }
+        self.x[i * 4 + j]
+    }
+
+    pub fn row(&self, i: usize) -> &[f64] {
+        &self.x[i * 4 .. (i + 1) * 4]
+    }
+
+    pub fn mul(&self, v: [f64; 4]) -> [f64; 4] {
+        let mut result = [0.0; 4];
+        for (i, r) in result.iter_mut().enumerate() {
+            *r = self.row(i).iter().zip(v.iter()).map(|(a, b)| a * b).sum();
         }
-        result 
-    }
-    
-    pub fn transpose(&self) -> Mat {
-        let mut transposed = Mat::zeros(self.cols, self.rows);
-        for i in 0 .. self.rows {
-            for j in 0 .. self.cols {
-                transposed.x[j * self.rows + i] = self.at(i, j);
-            }
+        result
+    }
+
+    // same as self.transpose().mul(v) without building the transpose
+    pub fn mul_transposed(&self, v: [f64; 4]) -> [f64; 4] {
+        let mut result = [0.0; 4];
+        for (j, r) in result.iter_mut().enumerate() {
+            *r = (0 .. 4).map(|i| self.at(i, j) * v[i]).sum();
         }
-        transposed
+        result
     }
 
-    pub fn submatrix(&self, row: usize, col: usize) -> Mat {
-        let mut submatrix = Mat::zeros(self.rows - 1, self.cols - 1);
-        let mut new_i = 0;
-        for i in 0 .. self.rows {
-            if i != row {
-                let mut new_j = 0;
-                for j in 0 .. self.cols {
-                    if j != col {
-                        submatrix.x[new_i * (self.cols - 1) + new_j] = self.at(i, j);
-                        new_j += 1;
-                    }
+    pub fn mat_mul(&self, other: &Mat4) -> Mat4 {
+        let mut result = Mat4::zeros();
+        for i in 0 .. 4 {
+            for j in 0 .. 4 {
+                for k in 0 .. 4 {
+                    result.x[i * 4 + j] += self.at(i, k) * other.at(k, j);
                 }
-                new_i += 1;
             }
         }
-        submatrix
+        result
     }
 
-    pub fn minor(&self, i: usize, j: usize) -> f64 {
-        assert!(self.cols == self.rows);
-        self.submatrix(i, j).determinant()
+    pub fn transpose(&self) -> Mat4 {
+        let mut transposed = Mat4::zeros();
+        for i in 0 .. 4 {
+            for j in 0 .. 4 {
+                transposed.x[j * 4 + i] = self.at(i, j);
+            }
+        }
+        transposed
     }
 
-    pub fn cofactor(&self, i: usize, j: usize) -> f64 {
-        assert!(self.cols == self.rows);
-        let sign = if (i + j) % 2 == 0 { 1.0 } else { -1.0 };
-        sign * self.minor(i, j)
+    // 2x2 determinants of the upper (s) and lower (c) row pairs,
+    // shared by the determinant and the adjugate
+    fn pair_determinants(&self) -> ([f64; 6], [f64; 6]) {
+        let a = &self.x;
+        let s = [
+            a[0] * a[5] - a[4] * a[1],
+            a[0] * a[6] - a[4] * a[2],
+            a[0] * a[7] - a[4] * a[3],
+            a[1] * a[6] - a[5] * a[2],
+            a[1] * a[7] - a[5] * a[3],
+            a[2] * a[7] - a[6] * a[3]
+        ];
+        let c = [
+            a[8] * a[13] - a[12] * a[9],
+            a[8] * a[14] - a[12] * a[10],
+            a[8] * a[15] - a[12] * a[11],
+            a[9] * a[14] - a[13] * a[10],
+            a[9] * a[15] - a[13] * a[11],
+            a[10] * a[15] - a[14] * a[11]
+        ];
+        (s, c)
     }
 
     pub fn determinant(&self) -> f64 {
-        assert!(self.cols == self.rows);
-        if self.rows == 2 {
-            self.at(0, 0) * self.at(1, 1) - self.at(0, 1) * self.at(1, 0)
-        } else {
-            let mut det = 0.0;
-            for column in 0 .. self.cols {
-                det += self.x[column] * self.cofactor(0, column);
-            }
-            det
-        }        
+        let (s, c) = self.pair_determinants();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
     }
 
-    pub fn inverse(&self) -> Option<Mat> {
-        assert!(self.cols == self.rows);
-        let det = self.determinant();
+    pub fn inverse(&self) -> Option<Mat4> {
+        let (s, c) = self.pair_determinants();
+        let det = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
         if det == 0.0 {
             None
         } else {
-            let mut inverted = Mat::zeros(self.cols, self.rows);
-            for row in 0 .. self.rows {
-                for col in 0 .. self.cols {
-                    let c = self.cofactor(row, col);
-                    inverted.x[col * self.rows + row] = c / det;
-                }
+            let a = &self.x;
+            let inv = 1.0 / det;
+            Some(Mat4::new([
+                ( a[5] * c[5] - a[6] * c[4] + a[7] * c[3]) * inv,
+                (-a[1] * c[5] + a[2] * c[4] - a[3] * c[3]) * inv,
+                ( a[13] * s[5] - a[14] * s[4] + a[15] * s[3]) * inv,
+                (-a[9] * s[5] + a[10] * s[4] - a[11] * s[3]) * inv,
+
+                (-a[4] * c[5] + a[6] * c[2] - a[7] * c[1]) * inv,
+                ( a[0] * c[5] - a[2] * c[2] + a[3] * c[1]) * inv,
+                (-a[12] * s[5] + a[14] * s[2] - a[15] * s[1]) * inv,
+                ( a[8] * s[5] - a[10] * s[2] + a[11] * s[1]) * inv,
+
+                ( a[4] * c[4] - a[5] * c[2] + a[7] * c[0]) * inv,
+                (-a[0] * c[4] + a[1] * c[2] - a[3] * c[0]) * inv,
+                ( a[12] * s[4] - a[13] * s[2] + a[15] * s[0]) * inv,
+                (-a[8] * s[4] + a[9] * s[2] - a[11] * s[0]) * inv,
+
+                (-a[4] * c[3] + a[5] * c[1] - a[6] * c[0]) * inv,
+                ( a[0] * c[3] - a[1] * c[1] + a[2] * c[0]) * inv,
+                (-a[12] * s[3] + a[13] * s[1] - a[14] * s[0]) * inv,
+                ( a[8] * s[3] - a[9] * s[1] + a[10] * s[0]) * inv
+            ]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &Mat4, b: &Mat4) {
+        for i in 0 .. 4 {
+            for j in 0 .. 4 {
+                assert!((a.at(i, j) - b.at(i, j)).abs() < 1e-5, "{:?} != {:?}", a, b);
             }
-            Some(inverted)
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn inverse() {
+        let m = Mat4::new([
+            -5.0,  2.0,  6.0, -8.0,
+             1.0, -5.0,  1.0,  8.0,
+             7.0,  7.0, -6.0, -7.0,
+             1.0, -3.0,  7.0,  4.0
+        ]);
+        assert!((m.determinant() - 532.0).abs() < 1e-9);
+        let expected = Mat4::new([
+             0.21805,  0.45113,  0.24060, -0.04511,
+            -0.80827, -1.45677, -0.44361,  0.52068,
+            -0.07895, -0.22368, -0.05263,  0.19737,
+            -0.52256, -0.81391, -0.30075,  0.30639
+        ]);
+        assert_close(&m.inverse().unwrap(), &expected);
+        assert_close(&m.mat_mul(&m.inverse().unwrap()), &Mat4::eye());
+    }
+
+    #[test]
+    fn inverse_undoes_product() {
+        let a = Mat4::new([
+             3.0, -9.0,  7.0,  3.0,
+             3.0, -8.0,  2.0, -9.0,
+            -4.0,  4.0,  4.0,  1.0,
+            -6.0,  5.0, -1.0,  1.0
+        ]);
+        let b = Mat4::new([
+            8.0,  2.0, 2.0, 2.0,
+            3.0, -1.0, 7.0, 0.0,
+            7.0,  0.0, 5.0, 4.0,
+            6.0, -2.0, 0.0, 5.0
+        ]);
+        assert_close(&a.mat_mul(&b).mat_mul(&b.inverse().unwrap()), &a);
+    }
+
+    #[test]
+    fn singular_has_no_inverse() {
+        let m = Mat4::new([
+            -4.0,  2.0, -2.0, -3.0,
+             9.0,  6.0,  2.0,  6.0,
+             0.0, -5.0,  1.0, -5.0,
+             0.0,  0.0,  0.0,  0.0
+        ]);
+        assert!(m.inverse().is_none());
+    }
+
+}
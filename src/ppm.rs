@@ -0,0 +1,47 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use crate::image::*;
+
+// plain (P3) ascii or binary (P6) 8 bit portable pixmap
+pub fn write_ppm(img: &Image, file: &str, binary: bool) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(file)?);
+    if binary {
+        write!(w, "P6\n{} {}\n255\n", img.w, img.h)?;
+        for y in 0 .. img.h {
+            for x in 0 .. img.w {
+                w.write_all(&img.rgb8(img.pixel(x, y))[.. 3])?;
+            }
+        }
+    } else {
+        write!(w, "P3\n{} {}\n255\n", img.w, img.h)?;
+        for y in 0 .. img.h {
+            let line: Vec<String> = (0 .. img.w)
+                .map(|x| {
+                    let [r, g, b, _] = img.rgb8(img.pixel(x, y));
+                    format!("{} {} {}", r, g, b)
+                })
+                .collect();
+            // the format asks for lines of at most 70 characters
+            for chunk in line.chunks(5) {
+                writeln!(w, "{}", chunk.join("  "))?;
+            }
+        }
+    }
+    w.flush()
+}
+
+// portable float map, little endian rows from bottom to top
+pub fn write_pfm(img: &Image, file: &str) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(file)?);
+    write!(w, "PF\n{} {}\n-1.0\n", img.w, img.h)?;
+    for y in (0 .. img.h).rev() {
+        for x in 0 .. img.w {
+            let c = img.pixel(x, y);
+            for v in [c.r, c.g, c.b].iter() {
+                w.write_all(&(*v as f32).to_le_bytes())?;
+            }
+        }
+    }
+    w.flush()
+}
@@ -0,0 +1,241 @@
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+use crate::image::*;
+use crate::aov::RenderPasses;
+use crate::raytracer::*;
+use crate::random::Rng;
+use crate::animation::frame_path;
+use crate::checkpoint::*;
+use crate::stats::*;
+
+// running sums of one sample per pixel and pass, the image at any point is
+// the mean so far
+pub struct Accumulation {
+    pub w: usize,
+    pub h: usize,
+    pub sum: Vec<Color>,
+    // squared luminance, for the variance of the mean
+    pub sum_sq: Vec<f64>,
+    pub passes: usize,
+    // hands out the seed of every pass
+    pub rng: Rng
+}
+
+fn luminance(c: &Color) -> f64 {
+    0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b
+}
+
+impl Accumulation {
+
+    pub fn new(w: usize, h: usize, seed: u64) -> Accumulation {
+        Accumulation {w, h, sum: vec![Color::black(); w * h], sum_sq: vec![0.0; w * h], passes: 0, rng: Rng::new(seed)}
+    }
+
+    pub fn add_pass(&mut self, tracer: &RayTracer, reflection_steps: usize) {
+        let seed = self.rng.next_u64();
+        for (cam, x0, y0) in tracer.views().iter() {
+            for y in 0 .. cam.vsize as usize {
+                for x in 0 .. cam.hsize as usize {
+                    let i = (y0 + y) * self.w + x0 + x;
+                    let mut rng = Rng::new(seed.wrapping_add(i as u64));
+                    if let Some(c) = tracer.sample_pixel(cam, x, y, &mut rng, reflection_steps) {
+                        self.sum_sq[i] += luminance(&c) * luminance(&c);
+                        self.sum[i] = self.sum[i].add(&c);
+                    }
+                }
+            }
+        }
+        self.passes += 1;
+    }
+
+    pub fn image(&self) -> Image {
+        let mut img = Image::new_rgba(self.w, self.h);
+        if self.passes > 0 {
+            let scale = 1.0 / self.passes as f64;
+            for (i, (_, _, c)) in img.pixels_mut().enumerate() {
+                *c = self.sum[i].scale(scale);
+            }
+        }
+        img
+    }
+
+    // mean relative standard error of the pixel luminance, an estimate of how
+    // far the image still is from converged
+    pub fn noise(&self) -> f64 {
+        if self.passes < 2 {
+            return f64::INFINITY;
+        }
+        let n = self.passes as f64;
+        let total: f64 = self.sum.iter().zip(self.sum_sq.iter())
+            .map(|(s, sq)| {
+                let mean = luminance(s) / n;
+                let variance = ((sq / n) - mean * mean).max(0.0) * n / (n - 1.0);
+                f64::sqrt(variance / n) / mean.max(1e-2)
+            })
+            .sum();
+        total / (self.w * self.h) as f64
+    }
+
+}
+
+// when to give up refining, whichever is reached first
+#[derive(Clone, Debug, Default)]
+pub struct StopCriteria {
+    pub passes: Option<usize>,
+    pub seconds: Option<f64>,
+    pub noise: Option<f64>
+}
+
+impl StopCriteria {
+
+    pub fn is_set(&self) -> bool {
+        self.passes.is_some() || self.seconds.is_some() || self.noise.is_some()
+    }
+
+    fn reached(&self, acc: &Accumulation, elapsed: f64) -> bool {
+        self.passes.is_some_and(|p| acc.passes >= p)
+            || self.seconds.is_some_and(|s| elapsed >= s)
+            || self.noise.is_some_and(|n| acc.noise() <= n)
+    }
+
+}
+
+// intermediate images written while rendering, a run of '#' in the path is
+// replaced by the pass count, otherwise the same file is overwritten
+#[derive(Clone, Debug)]
+pub struct Snapshots {
+    pub path: String,
+    pub every_passes: Option<usize>,
+    pub every_seconds: Option<f64>
+}
+
+impl Snapshots {
+
+    pub fn new(path: &str) -> Snapshots {
+        Snapshots {path: path.to_string(), every_passes: None, every_seconds: Some(10.0)}
+    }
+
+    fn path(&self, passes: usize) -> String {
+        if self.path.contains('#') { frame_path(&self.path, passes) } else { self.path.clone() }
+    }
+
+}
+
+// periodic dumps of the accumulation that an interrupted render can
+// resume from
+#[derive(Clone, Debug)]
+pub struct Checkpoints {
+    pub path: String,
+    pub every_passes: Option<usize>,
+    pub every_seconds: Option<f64>
+}
+
+impl Checkpoints {
+    pub fn new(path: &str) -> Checkpoints {
+        Checkpoints {path: path.to_string(), every_passes: None, every_seconds: Some(60.0)}
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Progressive {
+    pub stop: StopCriteria,
+    pub snapshots: Option<Snapshots>,
+    pub checkpoints: Option<Checkpoints>,
+    // continue from the checkpoint file if there is one
+    pub resume: bool,
+    // written to checkpoints so a resume can tell it is the same render
+    pub scene: String,
+    pub frame: usize,
+    pub seed: u64
+}
+
+fn due(last: (usize, f64), passes: usize, elapsed: f64, every_passes: Option<usize>, every_seconds: Option<f64>) -> bool {
+    every_passes.is_some_and(|n| passes - last.0 >= n) || every_seconds.is_some_and(|s| elapsed - last.1 >= s)
+}
+
+// the guides and passes only need a single sample, the beauty image is then
+// refined one sample per pixel at a time until the criteria are met or the
+// render is cancelled
+pub fn render_progressive(tracer: &RayTracer, settings: &RenderSettings, progressive: &Progressive, observer: &mut dyn Observer) -> io::Result<RenderPasses> {
+    let start = Instant::now();
+    let before = tracer.stats();
+    let passes = settings.render_passes();
+    let mut out = if passes.is_empty() {
+        let (w, h) = tracer.size();
+        RenderPasses::new(w, h, &[])
+    } else {
+        tracer.render_with(&RenderSettings {samples: Some(1), passes, denoiser: None, ..settings.clone()})
+    };
+    let fingerprint = Fingerprint {
+        scene: progressive.scene.clone(),
+        frame: progressive.frame,
+        reflection_steps: settings.reflection_steps,
+        seed: progressive.seed
+    };
+    let (mut acc, resumed_at) = match &progressive.checkpoints {
+        Some(checkpoints) if progressive.resume && Path::new(&checkpoints.path).exists() => {
+            let (acc, elapsed) = read_checkpoint(&checkpoints.path, &fingerprint, out.beauty.w, out.beauty.h)?;
+            println!("Resuming {} after pass {}", checkpoints.path, acc.passes);
+            (acc, elapsed)
+        },
+        _ => (Accumulation::new(out.beauty.w, out.beauty.h, progressive.seed), 0.0)
+    };
+    let stop = &progressive.stop;
+    let mut last_snapshot = (acc.passes, resumed_at);
+    let mut last_checkpoint = (acc.passes, resumed_at);
+    // a resumed render may already be done
+    while !(settings.cancel.is_cancelled() || stop.is_set() && stop.reached(&acc, resumed_at)) {
+        acc.add_pass(tracer, settings.reflection_steps);
+        let elapsed = resumed_at + start.elapsed().as_secs_f64();
+        let noise = acc.noise();
+        observer.progress(&Progress {done: acc.passes, total: stop.passes, unit: Unit::Passes, seconds: elapsed, noise: Some(noise)});
+        // without any criteria a single pass is all there is
+        let done = !stop.is_set() || stop.reached(&acc, elapsed) || settings.cancel.is_cancelled();
+        if let Some(checkpoints) = &progressive.checkpoints {
+            if done || due(last_checkpoint, acc.passes, elapsed, checkpoints.every_passes, checkpoints.every_seconds) {
+                write_checkpoint(&acc, &fingerprint, elapsed, &checkpoints.path)?;
+                last_checkpoint = (acc.passes, elapsed);
+            }
+        }
+        if let Some(snapshots) = &progressive.snapshots {
+            if !done && due(last_snapshot, acc.passes, elapsed, snapshots.every_passes, snapshots.every_seconds) {
+                let mut snapshot = out.clone();
+                snapshot.beauty = acc.image();
+                settings.finish(&mut snapshot);
+                snapshot.beauty.write(&snapshots.path(acc.passes))?;
+                last_snapshot = (acc.passes, elapsed);
+            }
+        }
+        if done {
+            break;
+        }
+    }
+    out.beauty = acc.image();
+    settings.finish(&mut out);
+    let mut stats = tracer.stats().since(&before, start.elapsed().as_secs_f64());
+    stats.cancelled = settings.cancel.is_cancelled();
+    observer.finished(&stats);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenes;
+
+    #[test]
+    fn noise_falls_with_passes() {
+        let tracer = scenes::default_world(16.0, 16.0);
+        let mut acc = Accumulation::new(16, 16, 3);
+        acc.add_pass(&tracer, 1);
+        assert!(acc.noise().is_infinite());
+        acc.add_pass(&tracer, 1);
+        let early = acc.noise();
+        for _ in 0 .. 30 {
+            acc.add_pass(&tracer, 1);
+        }
+        assert!(acc.noise() < early);
+    }
+
+}
@@ -0,0 +1,232 @@
+use crate::tuples::Vector3;
+use crate::matrices::Mat4;
+
+// which rotation is applied first, XYZ rotates about x, then y, then z
+// (all about the fixed world axes)
+#[derive(Clone, Copy, Debug)]
+pub enum EulerOrder {
+    XYZ,
+    XZY,
+    YXZ,
+    YZX,
+    ZXY,
+    ZYX
+}
+
+#[derive(Clone, Debug)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64
+}
+
+impl Quaternion {
+
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Quaternion {
+        Quaternion {w, x, y, z}
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    pub fn from_axis_angle(axis: &Vector3, by: f64) -> Quaternion {
+        let axis = axis.normalized();
+        let half = by.to_radians() / 2.0;
+        let s = f64::sin(half);
+        Quaternion::new(f64::cos(half), axis.x * s, axis.y * s, axis.z * s)
+    }
+
+    pub fn from_euler(x: f64, y: f64, z: f64, order: EulerOrder) -> Quaternion {
+        let qx = Quaternion::from_axis_angle(&Vector3::new(1.0, 0.0, 0.0), x);
+        let qy = Quaternion::from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), y);
+        let qz = Quaternion::from_axis_angle(&Vector3::new(0.0, 0.0, 1.0), z);
+        let (first, second, third) = match order {
+            EulerOrder::XYZ => (qx, qy, qz),
+            EulerOrder::XZY => (qx, qz, qy),
+            EulerOrder::YXZ => (qy, qx, qz),
+            EulerOrder::YZX => (qy, qz, qx),
+            EulerOrder::ZXY => (qz, qx, qy),
+            EulerOrder::ZYX => (qz, qy, qx)
+        };
+        third.mul(&second).mul(&first)
+    }
+
+    // rotation part of a matrix without scale or shear
+    pub fn from_mat(m: &Mat4) -> Quaternion {
+        let trace = m.at(0, 0) + m.at(1, 1) + m.at(2, 2);
+        let q = if trace > 0.0 {
+            let s = f64::sqrt(trace + 1.0) * 2.0;
+            Quaternion::new(
+                0.25 * s,
+                (m.at(2, 1) - m.at(1, 2)) / s,
+                (m.at(0, 2) - m.at(2, 0)) / s,
+                (m.at(1, 0) - m.at(0, 1)) / s
+            )
+        } else if m.at(0, 0) > m.at(1, 1) && m.at(0, 0) > m.at(2, 2) {
+            let s = f64::sqrt(1.0 + m.at(0, 0) - m.at(1, 1) - m.at(2, 2)) * 2.0;
+            Quaternion::new(
+                (m.at(2, 1) - m.at(1, 2)) / s,
+                0.25 * s,
+                (m.at(0, 1) + m.at(1, 0)) / s,
+                (m.at(0, 2) + m.at(2, 0)) / s
+            )
+        } else if m.at(1, 1) > m.at(2, 2) {
+            let s = f64::sqrt(1.0 + m.at(1, 1) - m.at(0, 0) - m.at(2, 2)) * 2.0;
+            Quaternion::new(
+                (m.at(0, 2) - m.at(2, 0)) / s,
+                (m.at(0, 1) + m.at(1, 0)) / s,
+                0.25 * s,
+                (m.at(1, 2) + m.at(2, 1)) / s
+            )
+        } else {
+            let s = f64::sqrt(1.0 + m.at(2, 2) - m.at(0, 0) - m.at(1, 1)) * 2.0;
+            Quaternion::new(
+                (m.at(1, 0) - m.at(0, 1)) / s,
+                (m.at(0, 2) + m.at(2, 0)) / s,
+                (m.at(1, 2) + m.at(2, 1)) / s,
+                0.25 * s
+            )
+        };
+        q.normalized()
+    }
+
+    // applying the result rotates by other first, then by self
+    pub fn mul(&self, other: &Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w
+        )
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn rotate(&self, v: &Vector3) -> Vector3 {
+        let p = Quaternion::new(0.0, v.x, v.y, v.z);
+        let r = self.mul(&p).mul(&self.conjugate());
+        Vector3::new(r.x, r.y, r.z)
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn scale(&self, scaler: f64) -> Quaternion {
+        Quaternion::new(self.w * scaler, self.x * scaler, self.y * scaler, self.z * scaler)
+    }
+
+    pub fn add(&self, other: &Quaternion) -> Quaternion {
+        Quaternion::new(self.w + other.w, self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+
+    pub fn normalized(&self) -> Quaternion {
+        self.scale(1.0 / f64::sqrt(self.dot(self)))
+    }
+
+    pub fn slerp(&self, other: &Quaternion, t: f64) -> Quaternion {
+        // take the short way around
+        let mut cos_theta = self.dot(other);
+        let other = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            other.scale(-1.0)
+        } else {
+            other.clone()
+        };
+        if cos_theta > 0.9995 {
+            self.scale(1.0 - t).add(&other.scale(t)).normalized()
+        } else {
+            let theta = f64::acos(cos_theta);
+            let sin_theta = f64::sin(theta);
+            let a = f64::sin((1.0 - t) * theta) / sin_theta;
+            let b = f64::sin(t * theta) / sin_theta;
+            self.scale(a).add(&other.scale(b))
+        }
+    }
+
+    pub fn to_mat(&self) -> Mat4 {
+        let q = self.normalized();
+        let (w, x, y, z) = (q.w, q.x, q.y, q.z);
+        Mat4::new([
+            1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z),       2.0 * (x * z + w * y),       0.0,
+            2.0 * (x * y + w * z),       1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x),       0.0,
+            2.0 * (x * z - w * y),       2.0 * (y * z + w * x),       1.0 - 2.0 * (x * x + y * y), 0.0,
+            0.0,                         0.0,                         0.0,                         1.0
+        ])
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transformations::Transformation;
+
+    fn assert_same_rotation(a: &Quaternion, b: &Mat4) {
+        let m = a.to_mat();
+        for i in 0 .. 4 {
+            for j in 0 .. 4 {
+                assert!((m.at(i, j) - b.at(i, j)).abs() < 1e-9, "{:?} != {:?}", m, b);
+            }
+        }
+    }
+
+    fn z_axis(degrees: f64) -> Quaternion {
+        Quaternion::from_axis_angle(&Vector3::new(0.0, 0.0, 1.0), degrees)
+    }
+
+    #[test]
+    fn slerp_ends_and_shortest_path() {
+        let a = z_axis(10.0);
+        let b = z_axis(100.0);
+        assert_same_rotation(&a.slerp(&b, 0.0), &a.to_mat());
+        assert_same_rotation(&a.slerp(&b, 1.0), &b.to_mat());
+        assert_same_rotation(&a.slerp(&b, 0.5), &z_axis(55.0).to_mat());
+        // 270 degrees one way is 90 the other, halfway is -45 and not 135
+        let long = z_axis(270.0);
+        assert!(Quaternion::identity().dot(&long) < 0.0);
+        assert_same_rotation(&Quaternion::identity().slerp(&long, 0.5), &z_axis(-45.0).to_mat());
+        assert_same_rotation(&Quaternion::identity().slerp(&long, 1.0), &long.to_mat());
+        // nearly the same rotation takes the linear branch
+        assert_same_rotation(&a.slerp(&z_axis(10.5), 0.5), &z_axis(10.25).to_mat());
+    }
+
+    #[test]
+    fn euler_orders_rotate_about_world_axes_in_turn() {
+        let (x, y, z) = (30.0, -50.0, 70.0);
+        let (rx, ry, rz) = (Transformation::rotate_x(x), Transformation::rotate_y(y), Transformation::rotate_z(z));
+        let orders = [
+            (EulerOrder::XYZ, rx.then(ry.clone()).then(rz.clone())),
+            (EulerOrder::XZY, rx.then(rz.clone()).then(ry.clone())),
+            (EulerOrder::YXZ, ry.then(rx.clone()).then(rz.clone())),
+            (EulerOrder::YZX, ry.then(rz.clone()).then(rx.clone())),
+            (EulerOrder::ZXY, rz.then(rx.clone()).then(ry.clone())),
+            (EulerOrder::ZYX, rz.then(ry).then(rx))
+        ];
+        for (order, expected) in orders.iter() {
+            assert_same_rotation(&Quaternion::from_euler(x, y, z, *order), &expected.transformation);
+        }
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        // a small turn and half turns about each axis, which take every
+        // branch of from_mat
+        let rotations = [
+            Quaternion::from_euler(20.0, 30.0, 40.0, EulerOrder::XYZ),
+            Quaternion::from_axis_angle(&Vector3::new(1.0, 0.0, 0.0), 180.0),
+            Quaternion::from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 180.0),
+            Quaternion::from_axis_angle(&Vector3::new(0.0, 0.0, 1.0), 180.0),
+            Quaternion::from_axis_angle(&Vector3::new(1.0, 2.0, -3.0), 170.0)
+        ];
+        for q in rotations.iter() {
+            let back = Quaternion::from_mat(&q.to_mat());
+            assert!((back.dot(q).abs() - 1.0).abs() < 1e-9, "{:?} != {:?}", back, q);
+        }
+    }
+
+}
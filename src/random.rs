@@ -0,0 +1,33 @@
+// xorshift64* generator, small and deterministic so renders
+// are reproducible from a seed
+#[derive(Clone, Debug)]
+pub struct Rng {
+    pub state: u64
+}
+
+impl Rng {
+
+    pub fn new(seed: u64) -> Rng {
+        // splitmix64 scrambles neighbouring seeds such as pixel indices
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        let state = if z == 0 { 0x2545_F491_4F6C_DD1D } else { z };
+        Rng {state}
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+}
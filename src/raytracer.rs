@@ -4,79 +4,61 @@ use crate::geometry::*;
 use crate::light::*;
 use crate::material::*;
 use crate::transformations::*;
+use crate::camera::*;
+use crate::random::Rng;
+use crate::aov::*;
+use crate::denoise::{Denoiser, GUIDES};
+use crate::tonemap::ToneMapping;
+use crate::stats::*;
+use std::time::Instant;
+use std::collections::HashMap;
 
-pub struct Camera { 
-    hsize: f64,
-    vsize: f64,    
-    half_width: f64,
-    half_height: f64,
-    pub pxl_sze: f64,    
-    cam_transform: Transformation
-}
-
-impl Camera {
-    pub fn default(hsize: f64, vsize: f64, fov: f64) -> Camera {
-        let cam_transform = Transformation::view(
-            Tuple4D::new_point(0.0, 0.0, 0.0), 
-            Tuple4D::new_point(0.0, 0.0, -1.0),
-            Tuple4D::new_vector(0.0, 1.0, 0.0));
-        Camera::new(hsize, vsize, fov, cam_transform)  
-    }
-    pub fn new(hsize: f64, vsize: f64, fov: f64, cam_transform: Transformation) -> Camera {        
-        let fov = fov.to_radians();
-        let half_view = f64::tan(fov / 2.0);
-        let aspect = hsize / vsize;
-        let half_width = if aspect >= 1.0 {
-            half_view
-        } else {
-            half_view * aspect
-        };
-        let half_height = if aspect >= 1.0 {
-            half_view / aspect
-        } else {
-            half_view
-        };
-        let pxl_sze = (half_width * 2.0) / hsize;
-        Camera {hsize, vsize, half_width, half_height, pxl_sze, cam_transform}
-    }
-    pub fn ray4pxl(&self, x: f64, y: f64) -> Ray {
-        let x_offset = (x + 0.5) * self.pxl_sze;
-        let y_offset = (y + 0.5) * self.pxl_sze;
-        let world_x  = self.half_width - x_offset; 
-        let world_y  = self.half_height - y_offset; 
-        let inv_transform = self.cam_transform.inverse().unwrap();
-        let pixel = inv_transform.transform(
-                &Tuple4D::new_point(world_x, world_y, -1.0)
-        );
-        let origin = inv_transform.transform(
-            &Tuple4D::new_point(0.0, 0.0, 0.0)
-        );
-        let direction = pixel.sub(&origin).normalized();
-        Ray::new(origin, direction)
-    }
-}
-
+// objects are known by their position in the world, which the world hands
+// out as they are added, and optionally by name
 pub struct World {
-    objects: Vec<Box<Primitive>>,
-    lights: Vec<PointLight>
+    objects: Vec<Box<dyn Primitive>>,
+    names: HashMap<String, usize>,
+    // the other way round, indexed by object
+    labels: Vec<Option<String>>,
+    lights: Vec<PointLight>,
+    counters: Counters
 }
 
 impl World {
-    pub fn new(objects: Vec<Box<Primitive>>, lights: Vec<PointLight>) -> World {
-        World{objects, lights}
-    }
-    pub fn default() -> World {
-        let light = PointLight::new(Color::new(1.0, 1.0, 1.0), Tuple4D::new_point(-10.0, 10.0, -10.0));
-        let mut world = World{objects: vec![], lights: vec![light]};
-        world.objects.push(Box::from(UnitSphere::new(0, Transformation::identity(), Material::new(Color::new(0.8, 1.0, 0.6), 0.1, 0.7, 0.2, 200.0, 0.0))));
-        world.objects.push(Box::from(UnitSphere::new(1, Transformation::scale(Tuple4D::new_vector(0.5, 0.5, 0.5)), Material::new(Color::new(1.0, 1.0, 1.0), 0.1, 0.7, 0.2, 200.0, 0.0))));
-        world
+    pub fn new(objects: Vec<Box<dyn Primitive>>, lights: Vec<PointLight>) -> World {
+        let labels = vec![None; objects.len()];
+        World{objects, names: HashMap::new(), labels, lights, counters: Counters::default()}
+    }
+    pub fn add(&mut self, object: Box<dyn Primitive>) -> usize {
+        self.objects.push(object);
+        self.labels.push(None);
+        self.objects.len() - 1
     }
-    pub fn is_shadowed(&self, light: &PointLight, point: &Tuple4D) -> bool {
-        let v = light.pos.sub(&point);
+    // a name given twice refers to the later object, the earlier one loses it
+    pub fn add_named(&mut self, name: &str, object: Box<dyn Primitive>) -> usize {
+        let id = self.add(object);
+        if let Some(earlier) = self.names.insert(name.to_string(), id) {
+            self.labels[earlier] = None;
+        }
+        self.labels[id] = Some(name.to_string());
+        id
+    }
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+    pub fn name(&self, id: usize) -> Option<&str> {
+        self.labels.get(id)?.as_deref()
+    }
+    // the top level object an intersection belongs to
+    pub fn object(&self, handle: &Handle) -> &dyn Primitive {
+        self.objects[handle.object].as_ref()
+    }
+    pub fn is_shadowed(&self, light: &PointLight, point: &Point3, time: f64) -> bool {
+        let v = light.pos - *point;
         let distance = v.magnitude();
         let direction = v.normalized();
-        let ray = Ray::new(point.clone(), direction);
+        let ray = Ray::new_at(*point, direction, time);
+        Counters::add(&self.counters.shadow_rays, 1);
         let intersections = self.intersect(&ray);
         let hit = Intersection::hit(&intersections);
         if let Some(hit) = hit {
@@ -87,12 +69,13 @@ impl World {
         }
     }
     pub fn reflected_color(&self, comps: &IntersectionPrecomp, steps_left: usize) -> Color {
-        let reflection = self.objects[comps.intersection.object_id].material().reflection;
+        let reflection = self.material(&comps.intersection).reflection;
         if  reflection == 0.0 || steps_left == 0 {
             Color::black()
         } else {
-            let point = comps.point.add(&comps.normal.scale(0.00001));
-            let reflect_ray = Ray::new(point, comps.reflection.clone());
+            let point = comps.point + comps.normal.to_vector() * 0.00001;
+            let reflect_ray = Ray::new_at(point, comps.reflection, comps.time);
+            Counters::add(&self.counters.reflection_rays, 1);
             let color = self.color_at(&reflect_ray, steps_left - 1);
             color.scale(reflection)
         }
@@ -101,55 +84,377 @@ impl World {
         let intersections = self.intersect(ray);
         let hit = Intersection::hit(&intersections);        
         if let Some(hit)= hit {
-            let precomp = IntersectionPrecomp::new(&hit, ray, self.objects[hit.object_id].as_ref());
+            let precomp = IntersectionPrecomp::new(&hit, ray, self.object(&hit.handle));
             self.shade_hit(&precomp, steps_left)            
         } else {
             Color::black()
         }
     }
+    // color_at for camera rays, keeping what the render passes need
+    pub fn sample(&self, ray: &Ray, steps_left: usize) -> Sample {
+        let intersections = self.intersect(ray);
+        let hit = match Intersection::hit(&intersections) {
+            Some(hit) => hit,
+            None => return Sample::miss(Color::black())
+        };
+        let object = self.object(&hit.handle);
+        let precomp = IntersectionPrecomp::new(&hit, ray, object);
+        let material = object.material_along(&hit.handle.path);
+        let mut sample = self.shade(&precomp, &material, steps_left);
+        sample.hit = true;
+        sample.depth = hit.dist;
+        sample.normal = precomp.normal;
+        sample.albedo = material.color;
+        sample.object_id = hit.handle.object;
+        sample
+    }
+    // the light leaving a hit with its terms kept apart, shade_hit only
+    // needs the total
+    fn shade(&self, precomp: &IntersectionPrecomp, material: &Material, steps_left: usize) -> Sample {
+        let mut sample = Sample::miss(Color::black());
+        let mut blocked = 0;
+        for light in self.lights.iter() {
+            let point = precomp.point + precomp.normal.to_vector() * 0.00001;
+            let in_shadow = self.is_shadowed(light, &point, precomp.time);
+            if in_shadow {
+                blocked += 1;
+            }
+            let shading = light.shade(material, &point, &precomp.eye, &precomp.normal, in_shadow);
+            sample.color = sample.color.add(&shading.total());
+            sample.diffuse = sample.diffuse.add(&shading.ambient).add(&shading.diffuse);
+            sample.specular = sample.specular.add(&shading.specular);
+        }
+        sample.reflection = self.reflected_color(precomp, steps_left);
+        sample.color = sample.color.add(&sample.reflection);
+        if !self.lights.is_empty() {
+            sample.shadow = blocked as f64 / self.lights.len() as f64;
+        }
+        sample
+    }
+    // the material of the part that was hit
+    pub fn material(&self, hit: &Intersection) -> Material {
+        self.object(&hit.handle).material_along(&hit.handle.path)
+    }
     pub fn intersect(&self, ray: &Ray) -> Vec<Intersection> {
         let mut intersections = vec![];
-        for object in self.objects.iter() {
-            intersections.extend(object.intersect(ray));
+        let before = shape_tests();
+        for (id, object) in self.objects.iter().enumerate() {
+            for mut intersection in object.intersect(ray) {
+                intersection.handle.object = id;
+                intersections.push(intersection);
+            }
         }
+        Counters::add(&self.counters.intersection_tests, shape_tests() - before);
         intersections.sort_by(|a, b| (a.dist).partial_cmp(&b.dist).unwrap());
         intersections
     }
     pub fn shade_hit(&self, precomp: &IntersectionPrecomp, steps_left: usize) -> Color {
-        let material = self.objects[precomp.intersection.object_id].material();
-        let mut color = Color::black();
-        for light in self.lights.iter() {
-            let point = precomp.point.add(&precomp.normal.scale(0.00001));
-            let in_shadow = self.is_shadowed(light, &point);
-            color = color.add(&light.lighting(&material, &point, &precomp.eye, &precomp.normal, in_shadow));
+        self.shade(precomp, &self.material(&precomp.intersection), steps_left).color
+    }
+}
+
+impl Default for World {
+    fn default() -> World {
+        let light = PointLight::new(Color::new(1.0, 1.0, 1.0), Point3::new(-10.0, 10.0, -10.0));
+        let mut world = World::new(vec![], vec![light]);
+        world.add(Box::from(UnitSphere::new(Transformation::identity(), Material::new(Color::new(0.8, 1.0, 0.6), 0.1, 0.7, 0.2, 200.0, 0.0))));
+        world.add(Box::from(UnitSphere::new(Transformation::scale(Vector3::new(0.5, 0.5, 0.5)), Material::new(Color::new(1.0, 1.0, 1.0), 0.1, 0.7, 0.2, 200.0, 0.0))));
+        world
+    }
+}
+
+// everything about a render that is not the scene itself
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    pub reflection_steps: usize,
+    // overrides the samples per pixel of the camera
+    pub samples: Option<usize>,
+    pub passes: Vec<Pass>,
+    pub denoiser: Option<Denoiser>,
+    pub tone_mapping: ToneMapping,
+    pub cancel: CancelToken
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings {reflection_steps: 5, samples: None, passes: vec![], denoiser: None, tone_mapping: ToneMapping::default(), cancel: CancelToken::new()}
+    }
+}
+
+impl RenderSettings {
+
+    // the requested passes plus the guides the denoiser needs
+    pub fn render_passes(&self) -> Vec<Pass> {
+        let mut passes = self.passes.clone();
+        if self.denoiser.is_some() {
+            for guide in GUIDES.iter() {
+                if !passes.contains(guide) {
+                    passes.push(*guide);
+                }
+            }
         }
-        let reflected = self.reflected_color(precomp, steps_left);
-        color.add(&reflected)
+        passes
     }
+
+    // denoises, drops the guides nobody asked for and sets the tone mapping
+    pub fn finish(&self, out: &mut RenderPasses) {
+        if let Some(denoiser) = self.denoiser {
+            out.beauty = denoiser.apply(out);
+            out.passes.retain(|(pass, _)| self.passes.contains(pass));
+        }
+        out.set_tone_mapping(&self.tone_mapping);
+    }
+
 }
 
 pub struct RayTracer {
     cam: Camera,
-    world: World
+    world: World,
+    stereo: Option<Stereo>
 }
 
 impl RayTracer {    
 
     pub fn new(cam: Camera, world: World) -> RayTracer {
-        RayTracer{cam, world}
+        RayTracer{cam, world, stereo: None}
     }   
 
-    pub fn trace(&self, reflection_steps: usize) -> Image {  
+    pub fn new_stereo(cam: Camera, world: World, stereo: Stereo) -> RayTracer {
+        RayTracer{cam, world, stereo: Some(stereo)}
+    }
+
+    pub fn trace(&self, reflection_steps: usize) -> Image {
+        self.trace_passes(reflection_steps, &[]).beauty
+    }
+
+    // the beauty image plus the given passes from the same camera rays
+    pub fn trace_passes(&self, reflection_steps: usize, passes: &[Pass]) -> RenderPasses {
+        self.trace_with(reflection_steps, self.cam.samples, passes, &mut Quiet, &CancelToken::new())
+    }
+
+    // renders the passes asked for, denoising with whatever guides it needs
+    // and handing back only what was requested
+    pub fn render_with(&self, settings: &RenderSettings) -> RenderPasses {
+        self.render_observed(settings, &mut Quiet)
+    }
+
+    // render_with reporting every finished row and the statistics at the
+    // end, a cancelled render leaves the remaining rows black
+    pub fn render_observed(&self, settings: &RenderSettings, observer: &mut dyn Observer) -> RenderPasses {
+        let start = Instant::now();
+        let before = self.stats();
+        let samples = settings.samples.unwrap_or(self.cam.samples);
+        let mut out = self.trace_with(settings.reflection_steps, samples, &settings.render_passes(), observer, &settings.cancel);
+        settings.finish(&mut out);
+        let mut stats = self.stats().since(&before, start.elapsed().as_secs_f64());
+        stats.cancelled = settings.cancel.is_cancelled();
+        observer.finished(&stats);
+        out
+    }
+
+    // everything traced since the tracer was made
+    pub fn stats(&self) -> RenderStats {
+        self.world.counters.snapshot()
+    }
+
+    // width and height of the final image, both eyes included
+    pub fn size(&self) -> (usize, usize) {
         let h = self.cam.hsize as usize;
         let v = self.cam.vsize as usize;
-        let mut img = Image::new_rgba(h, v);
-        for y in 0 .. v {
-            for x in 0 .. h {
-                let ray = self.cam.ray4pxl(x as f64, y as f64);
-                let color = self.world.color_at(&ray, reflection_steps);
-                img.set_rgb(x, y, &color);
+        match &self.stereo {
+            None => (h, v),
+            Some(Stereo { layout: StereoLayout::SideBySide, .. }) => (2 * h, v),
+            Some(Stereo { layout: StereoLayout::TopBottom, .. }) => (h, 2 * v)
+        }
+    }
+
+    // the cameras making up the image and the corner their pixels start at
+    pub fn views(&self) -> Vec<(Camera, usize, usize)> {
+        match &self.stereo {
+            None => vec![(self.cam.clone(), 0, 0)],
+            Some(stereo) => {
+                let left = self.cam.eye(Eye::Left, stereo);
+                let right = self.cam.eye(Eye::Right, stereo);
+                match stereo.layout {
+                    StereoLayout::SideBySide => vec![(left, 0, 0), (right, self.cam.hsize as usize, 0)],
+                    StereoLayout::TopBottom => vec![(left, 0, 0), (right, 0, self.cam.vsize as usize)]
+                }
+            }
+        }
+    }
+
+    // a single jittered sample through pixel (x, y) of one view
+    pub fn sample_pixel(&self, cam: &Camera, x: usize, y: usize, rng: &mut Rng, reflection_steps: usize) -> Option<Color> {
+        let ray = cam.ray4sample(x as f64, y as f64, rng)?;
+        Counters::add(&self.world.counters.primary_rays, 1);
+        Some(self.world.color_at(&ray, reflection_steps))
+    }
+
+    fn trace_with(&self, reflection_steps: usize, samples: usize, passes: &[Pass], observer: &mut dyn Observer, cancel: &CancelToken) -> RenderPasses {
+        let start = Instant::now();
+        let (w, h) = self.size();
+        let mut out = RenderPasses::new(w, h, passes);
+        let views = self.views();
+        let total = views.iter().map(|(cam, _, _)| cam.vsize as usize).sum();
+        let mut done = 0;
+        for (cam, x0, y0) in views.iter() {
+            for y in 0 .. cam.vsize as usize {
+                if cancel.is_cancelled() {
+                    return out;
+                }
+                self.render_row(cam, samples, &mut out, (*x0, *y0), y, reflection_steps);
+                done += 1;
+                observer.progress(&Progress {done, total: Some(total), unit: Unit::Rows, seconds: start.elapsed().as_secs_f64(), noise: None});
+            }
+        }
+        out
+    }
+
+    // beauty only color of pixel (x, y) of a view whose pixels start at the
+    // given corner in an image w pixels wide, None where the camera has no ray
+    fn pixel_color(&self, cam: &Camera, samples: usize, (x0, y0): (usize, usize), (x, y): (usize, usize), w: usize, reflection_steps: usize) -> Option<Color> {
+        if samples <= 1 {
+            let ray = cam.ray4pxl(x as f64, y as f64)?;
+            Counters::add(&self.world.counters.primary_rays, 1);
+            return Some(self.world.color_at(&ray, reflection_steps));
+        }
+        let mut rng = Rng::new(((y0 + y) * w + x0 + x) as u64);
+        let mut color = Color::black();
+        for _ in 0 .. samples {
+            if let Some(ray) = cam.ray4sample(x as f64, y as f64, &mut rng) {
+                Counters::add(&self.world.counters.primary_rays, 1);
+                color = color.add(&self.world.color_at(&ray, reflection_steps));
+            }
+        }
+        Some(color.scale(1.0 / samples as f64))
+    }
+
+    // row y of the view whose pixels start at the given corner
+    fn render_row(&self, cam: &Camera, samples: usize, out: &mut RenderPasses, (x0, y0): (usize, usize), y: usize, reflection_steps: usize) {
+        let h = cam.hsize as usize;
+        let w = out.beauty.w;
+        for x in 0 .. h {
+            if out.passes.is_empty() {
+                if let Some(color) = self.pixel_color(cam, samples, (x0, y0), (x, y), w, reflection_steps) {
+                    out.beauty.set_rgb(x0 + x, y0 + y, &color);
+                }
+            } else if samples <= 1 {
+                if let Some(ray) = cam.ray4pxl(x as f64, y as f64) {
+                    Counters::add(&self.world.counters.primary_rays, 1);
+                    let sample = self.world.sample(&ray, reflection_steps);
+                    out.beauty.set_rgb(x0 + x, y0 + y, &sample.color);
+                    for (pass, img) in out.passes.iter_mut() {
+                        img.set_rgb(x0 + x, y0 + y, &sample.pass(*pass));
+                    }
+                }
+            } else {
+                let mut rng = Rng::new(((y0 + y) * w + x0 + x) as u64);
+                let mut color = Color::black();
+                let mut sums = vec![Color {a: 0.0, ..Color::black()}; out.passes.len()];
+                for _ in 0 .. samples {
+                    if let Some(ray) = cam.ray4sample(x as f64, y as f64, &mut rng) {
+                        Counters::add(&self.world.counters.primary_rays, 1);
+                        let sample = self.world.sample(&ray, reflection_steps);
+                        color = color.add(&sample.color);
+                        for ((pass, _), sum) in out.passes.iter().zip(sums.iter_mut()) {
+                            let c = sample.pass(*pass);
+                            let a = sum.a + c.a;
+                            // ids do not average, the last sample that hit wins
+                            if *pass != Pass::ObjectId {
+                                *sum = sum.add(&c);
+                            } else if sample.hit {
+                                *sum = c;
+                            }
+                            sum.a = a;
+                        }
+                    }
+                }
+                let scale = 1.0 / samples as f64;
+                out.beauty.set_rgb(x0 + x, y0 + y, &color.scale(scale));
+                // the alpha of a pass ends up as the pixel coverage
+                for ((pass, img), sum) in out.passes.iter_mut().zip(sums.iter()) {
+                    let mut c = if *pass == Pass::ObjectId { sum.clone() } else { sum.scale(scale) };
+                    c.a = sum.a * scale;
+                    img.set_rgb(x0 + x, y0 + y, &c);
+                }
+            }
+        }
+    }
+
+    // the beauty pixels of a rectangle of the final image, the same values
+    // a full render gives them so tiles rendered apart fit together
+    pub fn render_tile(&self, settings: &RenderSettings, tx: usize, ty: usize, tw: usize, th: usize) -> Image {
+        let (w, _) = self.size();
+        let samples = settings.samples.unwrap_or(self.cam.samples);
+        let mut img = Image::new_rgba(tw, th);
+        for (cam, x0, y0) in self.views().iter() {
+            let (h, v) = (cam.hsize as usize, cam.vsize as usize);
+            for y in ty.max(*y0) .. (ty + th).min(y0 + v) {
+                for x in tx.max(*x0) .. (tx + tw).min(x0 + h) {
+                    if let Some(color) = self.pixel_color(cam, samples, (*x0, *y0), (x - x0, y - y0), w, settings.reflection_steps) {
+                        img.set_rgb(x - tx, y - ty, &color);
+                    }
+                }
             }
         }
         img
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::Group;
+
+    #[test]
+    fn hit_is_nearest_in_front_whatever_the_object_order() {
+        let sphere = |z: f64| -> Box<dyn Primitive> {
+            Box::new(UnitSphere::new(Transformation::translation(Vector3::new(0.0, 0.0, z)), Material::default()))
+        };
+        // far, behind the ray and near, in that order
+        let world = World::new(vec![sphere(10.0), sphere(-10.0), sphere(3.0)], vec![]);
+        let xs = world.intersect(&Ray::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0)));
+        assert_eq!(xs.iter().map(|i| i.dist).collect::<Vec<f64>>(), vec![-11.0, -9.0, 2.0, 4.0, 9.0, 11.0]);
+        let hit = Intersection::hit(&xs).unwrap();
+        assert_eq!((hit.dist, hit.handle.object), (2.0, 2));
+    }
+
+    #[test]
+    fn counts_the_shapes_tested_inside_groups() {
+        let row: Vec<Box<dyn Primitive>> = (0 .. 20)
+            .map(|i| Box::new(UnitSphere::new(Transformation::translation(Vector3::new(3.0 * i as f64, 0.0, 0.0)), Material::default())) as Box<dyn Primitive>)
+            .collect();
+        let world = World::new(vec![Box::new(Group::new(Transformation::identity(), row))], vec![]);
+        world.intersect(&Ray::new(Point3::new(6.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0)));
+        assert_eq!(world.counters.snapshot().intersection_tests, 1);
+        world.intersect(&Ray::new(Point3::new(-5.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0)));
+        assert_eq!(world.counters.snapshot().intersection_tests, 21);
+        world.intersect(&Ray::new(Point3::new(0.0, 5.0, -5.0), Vector3::new(0.0, 0.0, 1.0)));
+        assert_eq!(world.counters.snapshot().intersection_tests, 21);
+    }
+
+    #[test]
+    fn world_hands_out_ids_and_names() {
+        let mut world = World::new(vec![], vec![]);
+        let red = Material::from_color(Color::new(1.0, 0.0, 0.0));
+        let blue = Material::from_color(Color::new(0.0, 0.0, 1.0));
+        let ball = world.add_named("ball", Box::new(UnitSphere::new(Transformation::translation(Vector3::new(0.0, 0.0, 10.0)), red.clone())));
+        let pair = world.add_named("pair", Box::new(Group::new(Transformation::identity(), vec![
+            Box::new(UnitSphere::new(Transformation::translation(Vector3::new(0.0, 3.0, 0.0)), red)),
+            Box::new(UnitSphere::new(Transformation::identity(), blue))
+        ])));
+        assert_eq!((ball, pair), (0, 1));
+        assert_eq!(world.find("pair"), Some(pair));
+        assert_eq!(world.name(ball), Some("ball"));
+        assert_eq!(world.find("table"), None);
+        let xs = world.intersect(&Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0)));
+        let hit = Intersection::hit(&xs).unwrap();
+        assert_eq!(hit.handle, Handle {object: pair, path: vec![1]});
+        assert_eq!(world.material(&hit).color.b, 1.0);
+        assert_eq!(xs.last().unwrap().handle.object, ball);
+        let again = world.add_named("ball", Box::new(UnitSphere::new(Transformation::identity(), Material::default())));
+        assert_eq!(world.find("ball"), Some(again));
+        assert_eq!((world.name(ball), world.name(again), world.name(7)), (None, Some("ball"), None));
+    }
+
+}
@@ -0,0 +1,257 @@
+use crate::image::Color;
+use crate::tuples::*;
+use crate::transformations::*;
+use crate::geometry::*;
+use crate::material::*;
+use crate::light::*;
+use crate::camera::*;
+use crate::csg::*;
+use crate::group::Group;
+use crate::instance::Instance;
+use std::sync::Arc;
+use crate::quaternion::*;
+use crate::animation::*;
+use crate::raytracer::*;
+
+// the canonical scenes, shared by the command line, the golden image tests
+// and render workers which look them up by name
+pub const NAMES: [&str; 6] = ["default", "room", "cornell", "reflections", "csg", "forest"];
+
+pub fn by_name(name: &str, hsize: f64, vsize: f64) -> Option<RayTracer> {
+    frame_by_name(name, hsize, vsize, 0.0)
+}
+
+// the scenes that do not move look the same in every frame
+pub fn frame_by_name(name: &str, hsize: f64, vsize: f64, frame: f64) -> Option<RayTracer> {
+    match name {
+        "default" => Some(default_world(hsize, vsize)),
+        "room" => Some(room(hsize, vsize).frame(frame)),
+        "cornell" => Some(cornell_box(hsize, vsize)),
+        "reflections" => Some(reflections(hsize, vsize)),
+        "csg" => Some(csg(hsize, vsize)),
+        "forest" => Some(forest(hsize, vsize)),
+        _ => None
+    }
+}
+
+// the two nested spheres of World::default seen from the front
+pub fn default_world(hsize: f64, vsize: f64) -> RayTracer {
+    let view = Transformation::view(Point3::new(0.0, 0.0, -5.0), Point3::origin(), Vector3::new(0.0, 1.0, 0.0));
+    RayTracer::new(Camera::new(hsize, vsize, 45.0, view), World::default())
+}
+
+fn wall(angle: f64) -> AnimatedObject {
+    AnimatedObject::fixed(move |t| Box::new(Plane::new(
+        Transformation::chain(&[
+            t,
+            Transformation::translation(Vector3::new(0.0, 0.0, 5.0)),
+            Transformation::rotate_y(angle.to_degrees()),
+            Transformation::rotate_x((std::f64::consts::PI / 2.0).to_degrees()),
+        ]),
+        Material::new(Color::new(1.0, 0.9, 0.9), 0.1, 0.9, 0.0, 200.0, 0.5)
+    )))
+}
+
+pub fn room(hsize: f64, vsize: f64) -> AnimatedScene {
+    let floor = AnimatedObject::fixed(|t| Box::new(Plane::new(
+        t,
+        Material::new(Color::new(1.0, 0.9, 0.9), 0.1, 0.9, 0.0, 200.0, 0.5)
+    )));
+    let left_wall = wall(- std::f64::consts::PI / 4.0);
+    let right_wall = wall(std::f64::consts::PI / 4.0);
+    // the middle sphere spins and bobs once over 120 frames
+    let middle = AnimatedObject::new(
+        TransformCurve::new(
+            Curve::new(vec![
+                Key::new(0.0,   Vector3::new(-0.5, 1.0, 0.5), Interpolation::EaseInOut),
+                Key::new(60.0,  Vector3::new(-0.5, 1.5, 0.5), Interpolation::EaseInOut),
+                Key::new(120.0, Vector3::new(-0.5, 1.0, 0.5), Interpolation::Linear)
+            ]),
+            Curve::new(vec![
+                Key::new(0.0,   Quaternion::identity(), Interpolation::Linear),
+                Key::new(60.0,  Quaternion::from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 180.0), Interpolation::Linear),
+                Key::new(120.0, Quaternion::from_axis_angle(&Vector3::new(0.0, 1.0, 0.0), 360.0), Interpolation::Linear)
+            ]),
+            Curve::constant(Vector3::new(1.0, 1.0, 1.0))
+        ),
+        |t| Box::new(UnitSphere::new(
+            t,
+            Material::new(Color::new(0.1, 1.0, 0.5), 0.1, 0.7, 0.3, 200.0, 0.0)
+        ))
+    );
+    let right = AnimatedObject::fixed(|t| Box::new(UnitSphere::new(
+        Transformation::chain(&[
+            t,
+            Transformation::translation(Vector3::new(1.5, 0.5, -1.5)),
+            Transformation::scale(Vector3::new(0.5, 0.5, 0.5))
+        ]),
+        Material::new(Color::new(0.1, 1.0, 0.5), 0.1, 0.7, 0.3, 200.0, 0.0)
+    )));
+    let left = AnimatedObject::fixed(|t| Box::new(UnitSphere::new(
+        Transformation::chain(&[
+            t,
+            Transformation::translation(Vector3::new(-1.5, 0.33, -0.75)),
+            Transformation::scale(Vector3::new(0.33, 0.33, 0.33))
+        ]),
+        Material::new(Color::new(1.0, 0.8, 0.1), 0.1, 0.7, 0.3, 200.0, 0.0)
+    )));
+    let light = AnimatedLight::new(
+        Color::new(1.0, 1.0, 1.0), Curve::constant(Point3::new(-10.0, 10.0, -10.0)));
+    // the camera dollies to the right and back again
+    let cam = AnimatedCamera::new(
+        hsize,
+        vsize,
+        Curve::new(vec![
+            Key::new(0.0,   Point3::new(0.0, 2.5, -8.0), Interpolation::EaseInOut),
+            Key::new(60.0,  Point3::new(3.0, 2.5, -7.5), Interpolation::EaseInOut),
+            Key::new(120.0, Point3::new(0.0, 2.5, -8.0), Interpolation::Linear)
+        ]),
+        Curve::constant(Point3::new(0.0, 1.0, 0.0)),
+        Curve::constant(Vector3::new(0.0, 1.0, 0.0)),
+        Curve::constant((std::f64::consts::PI / 3.0).to_degrees())
+    );
+    AnimatedScene::new(
+        cam,
+        vec![light],
+        vec![floor, left_wall, right_wall, middle, right, left]
+    )
+}
+
+// red and green side walls around two spheres, lit from just below the
+// ceiling, the front is left open for the camera
+pub fn cornell_box(hsize: f64, vsize: f64) -> RayTracer {
+    let white = Material::new(Color::new(0.73, 0.73, 0.73), 0.1, 0.9, 0.0, 200.0, 0.0);
+    let red = Material::new(Color::new(0.65, 0.05, 0.05), 0.1, 0.9, 0.0, 200.0, 0.0);
+    let green = Material::new(Color::new(0.12, 0.45, 0.15), 0.1, 0.9, 0.0, 200.0, 0.0);
+    let objects: Vec<Box<dyn Primitive>> = vec![
+        Box::new(Plane::new(Transformation::identity(), white.clone())),
+        Box::new(Plane::new(Transformation::translation(Vector3::new(0.0, 2.0, 0.0)), white.clone())),
+        Box::new(Plane::new(Transformation::chain(&[
+            Transformation::translation(Vector3::new(0.0, 0.0, 1.0)),
+            Transformation::rotate_x(90.0)
+        ]), white.clone())),
+        Box::new(Plane::new(Transformation::chain(&[
+            Transformation::translation(Vector3::new(-1.0, 0.0, 0.0)),
+            Transformation::rotate_z(90.0)
+        ]), red)),
+        Box::new(Plane::new(Transformation::chain(&[
+            Transformation::translation(Vector3::new(1.0, 0.0, 0.0)),
+            Transformation::rotate_z(90.0)
+        ]), green)),
+        Box::new(UnitSphere::new(Transformation::chain(&[
+            Transformation::translation(Vector3::new(-0.4, 0.45, 0.3)),
+            Transformation::scale(Vector3::new(0.45, 0.45, 0.45))
+        ]), Material::new(Color::new(0.73, 0.73, 0.73), 0.1, 0.9, 0.3, 100.0, 0.0))),
+        Box::new(UnitSphere::new(Transformation::chain(&[
+            Transformation::translation(Vector3::new(0.45, 0.3, -0.3)),
+            Transformation::scale(Vector3::new(0.3, 0.3, 0.3))
+        ]), Material::new(Color::new(0.73, 0.73, 0.73), 0.1, 0.9, 0.3, 100.0, 0.0)))
+    ];
+    let light = PointLight::new(Color::new(1.0, 1.0, 1.0), Point3::new(0.0, 1.9, 0.0));
+    let view = Transformation::view(Point3::new(0.0, 1.0, -3.4), Point3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+    RayTracer::new(Camera::new(hsize, vsize, 45.0, view), World::new(objects, vec![light]))
+}
+
+// a chrome sphere between two colored ones on a mirror floor, so light
+// bounces between them until the reflection depth runs out
+pub fn reflections(hsize: f64, vsize: f64) -> RayTracer {
+    let objects: Vec<Box<dyn Primitive>> = vec![
+        Box::new(Plane::new(Transformation::identity(), Material::new(Color::new(0.2, 0.2, 0.3), 0.1, 0.6, 0.0, 200.0, 0.6))),
+        Box::new(Plane::new(Transformation::chain(&[
+            Transformation::translation(Vector3::new(0.0, 0.0, 6.0)),
+            Transformation::rotate_x(90.0)
+        ]), Material::new(Color::new(0.9, 0.9, 0.8), 0.1, 0.9, 0.0, 200.0, 0.0))),
+        Box::new(UnitSphere::new(Transformation::translation(Vector3::new(0.0, 1.0, 0.0)),
+            Material::new(Color::new(0.1, 0.1, 0.1), 0.0, 0.1, 1.0, 300.0, 0.9))),
+        Box::new(UnitSphere::new(Transformation::chain(&[
+            Transformation::translation(Vector3::new(-2.0, 0.6, -0.5)),
+            Transformation::scale(Vector3::new(0.6, 0.6, 0.6))
+        ]), Material::new(Color::new(1.0, 0.2, 0.1), 0.1, 0.8, 0.3, 200.0, 0.2))),
+        Box::new(UnitSphere::new(Transformation::chain(&[
+            Transformation::translation(Vector3::new(2.0, 0.6, -0.5)),
+            Transformation::scale(Vector3::new(0.6, 0.6, 0.6))
+        ]), Material::new(Color::new(0.1, 0.3, 1.0), 0.1, 0.8, 0.3, 200.0, 0.2)))
+    ];
+    let light = PointLight::new(Color::new(1.0, 1.0, 1.0), Point3::new(-5.0, 8.0, -6.0));
+    let view = Transformation::view(Point3::new(0.0, 2.0, -6.0), Point3::new(0.0, 0.8, 0.0), Vector3::new(0.0, 1.0, 0.0));
+    RayTracer::new(Camera::new(hsize, vsize, 50.0, view), World::new(objects, vec![light]))
+}
+
+// a bowl hollowed out of a sphere and a thick wall with a round hole that
+// shows the backdrop behind it
+pub fn csg(hsize: f64, vsize: f64) -> RayTracer {
+    let clay = Material::new(Color::new(0.9, 0.5, 0.2), 0.1, 0.8, 0.3, 100.0, 0.0);
+    let stone = Material::new(Color::new(0.7, 0.7, 0.75), 0.1, 0.9, 0.0, 200.0, 0.0);
+    let ball = |center: Vector3, radius: f64, material: &Material| -> Box<dyn Primitive> {
+        Box::new(UnitSphere::new(Transformation::chain(&[
+            Transformation::translation(center),
+            Transformation::scale(Vector3::new(radius, radius, radius))
+        ]), material.clone()))
+    };
+    // planes are solid on the side away from their normal
+    let half_space = |transformation: Transformation, material: &Material| -> Box<dyn Primitive> {
+        Box::new(Plane::new(transformation, material.clone()))
+    };
+    let center = Vector3::new(-1.0, 0.8, -0.5);
+    let shell = Csg::new(CsgOp::Difference, ball(center, 0.8, &clay), ball(center, 0.7, &clay));
+    let above = half_space(Transformation::chain(&[
+        Transformation::translation(Vector3::new(0.0, 0.9, 0.0)),
+        Transformation::rotate_x(180.0)
+    ]), &clay);
+    let bowl = Csg::new(CsgOp::Difference, Box::new(shell), above);
+    let slab = Csg::new(CsgOp::Intersection,
+        half_space(Transformation::chain(&[
+            Transformation::translation(Vector3::new(0.0, 0.0, 1.5)),
+            Transformation::rotate_x(-90.0)
+        ]), &stone),
+        half_space(Transformation::chain(&[
+            Transformation::translation(Vector3::new(0.0, 0.0, 1.8)),
+            Transformation::rotate_x(90.0)
+        ]), &stone));
+    let wall = Csg::new(CsgOp::Difference, Box::new(slab), ball(Vector3::new(1.0, 1.3, 1.65), 0.7, &stone));
+    let light = PointLight::new(Color::new(1.0, 1.0, 1.0), Point3::new(-4.0, 6.0, -6.0));
+    let mut world = World::new(vec![], vec![light]);
+    world.add_named("bowl", Box::new(bowl));
+    world.add_named("wall", Box::new(wall));
+    world.add_named("floor", Box::new(Plane::new(Transformation::identity(), Material::new(Color::new(0.9, 0.9, 0.85), 0.1, 0.9, 0.0, 200.0, 0.0))));
+    world.add_named("backdrop", Box::new(Plane::new(Transformation::chain(&[
+        Transformation::translation(Vector3::new(0.0, 0.0, 5.0)),
+        Transformation::rotate_x(90.0)
+    ]), Material::new(Color::new(0.2, 0.4, 0.8), 0.3, 0.7, 0.0, 200.0, 0.0))));
+    let view = Transformation::view(Point3::new(0.5, 2.8, -4.5), Point3::new(0.0, 0.9, 0.8), Vector3::new(0.0, 1.0, 0.0));
+    RayTracer::new(Camera::new(hsize, vsize, 60.0, view), world)
+}
+
+// one tree planted thirty times, a few of them in autumn colors
+pub fn forest(hsize: f64, vsize: f64) -> RayTracer {
+    let bark = Material::new(Color::new(0.4, 0.25, 0.1), 0.1, 0.9, 0.0, 200.0, 0.0);
+    let leaves = Material::new(Color::new(0.15, 0.55, 0.15), 0.1, 0.9, 0.1, 50.0, 0.0);
+    let autumn = Material::new(Color::new(0.85, 0.45, 0.1), 0.1, 0.9, 0.1, 50.0, 0.0);
+    let tree: Arc<dyn Primitive> = Arc::new(Group::new(Transformation::identity(), vec![
+        Box::new(UnitSphere::new(Transformation::chain(&[
+            Transformation::translation(Vector3::new(0.0, 0.6, 0.0)),
+            Transformation::scale(Vector3::new(0.1, 0.6, 0.1))
+        ]), bark.clone())),
+        Box::new(UnitSphere::new(Transformation::chain(&[
+            Transformation::translation(Vector3::new(0.0, 1.4, 0.0)),
+            Transformation::scale(Vector3::new(0.5, 0.6, 0.5))
+        ]), leaves))
+    ]));
+    let mut trees: Vec<Box<dyn Primitive>> = vec![];
+    for i in 0 .. 30 {
+        let (row, column) = ((i / 6) as f64, (i % 6) as f64);
+        let size = 0.8 + 0.1 * ((i * 7) % 5) as f64;
+        let placed = Instance::new(Transformation::chain(&[
+            Transformation::translation(Vector3::new(column * 1.3 - 3.5 + 0.4 * row, 0.0, row * 1.5)),
+            Transformation::rotate_y(37.0 * i as f64),
+            Transformation::scale(Vector3::new(size, size, size))
+        ]), tree.clone());
+        trees.push(Box::new(if i % 4 == 1 { placed.with_material(autumn.clone()) } else { placed }));
+    }
+    let light = PointLight::new(Color::new(1.0, 1.0, 1.0), Point3::new(-6.0, 10.0, -8.0));
+    let mut world = World::new(vec![], vec![light]);
+    world.add_named("ground", Box::new(Plane::new(Transformation::identity(), Material::new(Color::new(0.5, 0.6, 0.3), 0.1, 0.9, 0.0, 200.0, 0.0))));
+    world.add_named("trees", Box::new(Group::new(Transformation::identity(), trees)));
+    let view = Transformation::view(Point3::new(0.0, 3.0, -5.0), Point3::new(0.0, 0.5, 3.0), Vector3::new(0.0, 1.0, 0.0));
+    RayTracer::new(Camera::new(hsize, vsize, 60.0, view), world)
+}
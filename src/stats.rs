@@ -0,0 +1,221 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// running totals kept by the world while it traces, atomics so a shared
+// world can be counted from several threads
+#[derive(Debug, Default)]
+pub struct Counters {
+    pub primary_rays: AtomicU64,
+    pub shadow_rays: AtomicU64,
+    pub reflection_rays: AtomicU64,
+    pub intersection_tests: AtomicU64
+}
+
+impl Counters {
+
+    pub fn add(counter: &AtomicU64, n: u64) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> RenderStats {
+        RenderStats {
+            primary_rays: self.primary_rays.load(Ordering::Relaxed),
+            shadow_rays: self.shadow_rays.load(Ordering::Relaxed),
+            reflection_rays: self.reflection_rays.load(Ordering::Relaxed),
+            intersection_tests: self.intersection_tests.load(Ordering::Relaxed),
+            seconds: 0.0,
+            cancelled: false
+        }
+    }
+
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    pub primary_rays: u64,
+    pub shadow_rays: u64,
+    pub reflection_rays: u64,
+    pub intersection_tests: u64,
+    // wall time
+    pub seconds: f64,
+    pub cancelled: bool
+}
+
+impl RenderStats {
+
+    // what happened between an earlier snapshot and this one
+    pub fn since(&self, before: &RenderStats, seconds: f64) -> RenderStats {
+        RenderStats {
+            primary_rays: self.primary_rays - before.primary_rays,
+            shadow_rays: self.shadow_rays - before.shadow_rays,
+            reflection_rays: self.reflection_rays - before.reflection_rays,
+            intersection_tests: self.intersection_tests - before.intersection_tests,
+            seconds,
+            cancelled: self.cancelled
+        }
+    }
+
+    // totals of renders done apart, on several workers say
+    pub fn add(&mut self, other: &RenderStats) {
+        self.primary_rays += other.primary_rays;
+        self.shadow_rays += other.shadow_rays;
+        self.reflection_rays += other.reflection_rays;
+        self.intersection_tests += other.intersection_tests;
+    }
+
+    pub fn rays(&self) -> u64 {
+        self.primary_rays + self.shadow_rays + self.reflection_rays
+    }
+
+    pub fn tests_per_ray(&self) -> f64 {
+        if self.rays() == 0 { 0.0 } else { self.intersection_tests as f64 / self.rays() as f64 }
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        if self.seconds > 0.0 { self.rays() as f64 / self.seconds } else { 0.0 }
+    }
+
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} rays: {} primary, {} shadow, {} reflection",
+            self.rays(), self.primary_rays, self.shadow_rays, self.reflection_rays)?;
+        writeln!(f, "{} intersection tests, {:.1} per ray", self.intersection_tests, self.tests_per_ray())?;
+        write!(f, "{:.2}s wall time, {:.0} rays/s{}", self.seconds, self.rays_per_second(),
+            if self.cancelled { ", cancelled" } else { "" })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Unit {
+    Rows,
+    Passes,
+    Tiles
+}
+
+impl Unit {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Unit::Rows => "rows",
+            Unit::Passes => "passes",
+            Unit::Tiles => "tiles"
+        }
+    }
+}
+
+// how far a render has come, in rows for a single image, in passes for
+// progressive renders and in tiles for distributed ones
+#[derive(Clone, Debug)]
+pub struct Progress {
+    pub done: usize,
+    // unknown for progressive renders that stop on time or noise
+    pub total: Option<usize>,
+    pub unit: Unit,
+    pub seconds: f64,
+    pub noise: Option<f64>
+}
+
+impl Progress {
+
+    // seconds left, assuming the rest goes as fast as what is done
+    pub fn eta(&self) -> Option<f64> {
+        match self.total {
+            Some(total) if self.done > 0 => Some(self.seconds / self.done as f64 * (total - self.done) as f64),
+            _ => None
+        }
+    }
+
+}
+
+pub trait Observer {
+    fn progress(&mut self, _progress: &Progress) {}
+    fn finished(&mut self, _stats: &RenderStats) {}
+}
+
+// ignores everything
+pub struct Quiet;
+
+impl Observer for Quiet {}
+
+// prints every tenth of the rows or tiles, every pass and the statistics at
+// the end
+#[derive(Default)]
+pub struct ConsoleObserver {
+    last_tenth: usize
+}
+
+impl Observer for ConsoleObserver {
+
+    fn progress(&mut self, p: &Progress) {
+        let eta = p.eta().map_or(String::new(), |s| format!(", {:.1}s left", s));
+        match p.total {
+            Some(total) if p.unit != Unit::Passes => {
+                let tenth = p.done * 10 / total.max(1);
+                if tenth > self.last_tenth || p.done == total {
+                    self.last_tenth = tenth;
+                    println!("{:3}% ({}/{} {}) {:.1}s{}", p.done * 100 / total.max(1), p.done, total, p.unit.name(), p.seconds, eta);
+                }
+            },
+            _ => {
+                let noise = p.noise.map_or(String::new(), |n| format!(" noise {:.4}", n));
+                println!("Pass {}{} {:.1}s{}", p.done, noise, p.seconds, eta);
+            }
+        }
+    }
+
+    fn finished(&mut self, stats: &RenderStats) {
+        println!("{}", stats);
+    }
+
+}
+
+// shared flag a render checks between rows or passes, cancelling keeps what
+// has been rendered so far
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>
+}
+
+impl CancelToken {
+
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scenes;
+    use crate::raytracer::RenderSettings;
+
+    #[test]
+    fn counts_rays() {
+        let tracer = scenes::default_world(8.0, 6.0);
+        tracer.render_with(&RenderSettings::default());
+        let stats = tracer.stats();
+        assert_eq!(stats.primary_rays, 48);
+        assert!(stats.shadow_rays > 0 && stats.shadow_rays <= 48);
+        assert!(stats.tests_per_ray() >= 1.0);
+    }
+
+    #[test]
+    fn cancelled_render_stops_early() {
+        let tracer = scenes::default_world(8.0, 6.0);
+        let settings = RenderSettings::default();
+        settings.cancel.cancel();
+        tracer.render_with(&settings);
+        assert_eq!(tracer.stats().primary_rays, 0);
+    }
+
+}
@@ -0,0 +1,135 @@
+use crate::image::Color;
+use crate::colorspace::Transfer;
+
+// operators compressing the unbounded scene radiance into [0, 1]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMap {
+    // hard clip at 1.0, highlights blow out
+    Clip,
+    Reinhard,
+    // reinhard reaching 1.0 at the white point instead of infinity
+    ReinhardExtended { white: f64 },
+    // Narkowicz's fit of the ACES filmic curve
+    Aces,
+    // Hable's Uncharted 2 filmic curve
+    Hable
+}
+
+impl ToneMap {
+
+    // accepts clip, reinhard, reinhard-extended[:white], aces and hable,
+    // the white point must be above zero
+    pub fn parse(name: &str) -> Option<ToneMap> {
+        let mut parts = name.splitn(2, ':');
+        match (parts.next()?, parts.next()) {
+            ("clip", None) => Some(ToneMap::Clip),
+            ("reinhard", None) => Some(ToneMap::Reinhard),
+            ("reinhard-extended", None) => Some(ToneMap::ReinhardExtended { white: 4.0 }),
+            ("reinhard-extended", Some(white)) => white.parse().ok()
+                .filter(|white: &f64| *white > 0.0 && white.is_finite())
+                .map(|white| ToneMap::ReinhardExtended { white }),
+            ("aces", None) => Some(ToneMap::Aces),
+            ("hable", None) => Some(ToneMap::Hable),
+            _ => None
+        }
+    }
+
+    fn hable_partial(x: f64) -> f64 {
+        let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+        ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+    }
+
+    pub fn map(&self, x: f64) -> f64 {
+        let x = x.max(0.0);
+        match *self {
+            ToneMap::Clip => x.min(1.0),
+            ToneMap::Reinhard => x / (1.0 + x),
+            ToneMap::ReinhardExtended { white } => (x * (1.0 + x / (white * white)) / (1.0 + x)).min(1.0),
+            ToneMap::Aces => ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0),
+            ToneMap::Hable => {
+                let exposure_bias = 2.0;
+                let white = 11.2;
+                // past the white point the curve keeps rising above 1
+                (ToneMap::hable_partial(x * exposure_bias) / ToneMap::hable_partial(white)).min(1.0)
+            }
+        }
+    }
+
+}
+
+// exposure, then the operator, then the display encoding
+#[derive(Clone, Debug)]
+pub struct ToneMapping {
+    // in stops, every +1 doubles the light
+    pub exposure: f64,
+    pub operator: ToneMap,
+    pub transfer: Transfer
+}
+
+impl ToneMapping {
+
+    pub fn new(exposure: f64, operator: ToneMap, transfer: Transfer) -> ToneMapping {
+        ToneMapping {exposure, operator, transfer}
+    }
+
+    pub fn apply(&self, c: &Color) -> Color {
+        let gain = f64::powf(2.0, self.exposure);
+        let mut mapped = Color::new(
+            self.transfer.encode(self.operator.map(c.r * gain)),
+            self.transfer.encode(self.operator.map(c.g * gain)),
+            self.transfer.encode(self.operator.map(c.b * gain))
+        );
+        mapped.a = c.a;
+        mapped
+    }
+
+}
+
+impl Default for ToneMapping {
+    fn default() -> ToneMapping {
+        ToneMapping::new(0.0, ToneMap::Clip, Transfer::Srgb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_operators() {
+        assert_eq!(ToneMap::parse("aces"), Some(ToneMap::Aces));
+        assert_eq!(ToneMap::parse("reinhard-extended"), Some(ToneMap::ReinhardExtended { white: 4.0 }));
+        assert_eq!(ToneMap::parse("reinhard-extended:2.5"), Some(ToneMap::ReinhardExtended { white: 2.5 }));
+        for bad in ["reinhard-extended:0", "reinhard-extended:-1", "reinhard-extended:inf", "reinhard-extended:x", "reinhard:2", "filmic"].iter() {
+            assert_eq!(ToneMap::parse(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn known_values() {
+        assert_eq!(ToneMap::Clip.map(2.0), 1.0);
+        assert_eq!(ToneMap::Clip.map(0.25), 0.25);
+        assert_eq!(ToneMap::Reinhard.map(1.0), 0.5);
+        assert_eq!(ToneMap::Reinhard.map(3.0), 0.75);
+        let extended = ToneMap::ReinhardExtended { white: 4.0 };
+        assert!((extended.map(4.0) - 1.0).abs() < 1e-12);
+        assert_eq!(extended.map(10.0), 1.0);
+        // negative light is treated as none
+        assert_eq!(ToneMap::Reinhard.map(-1.0), 0.0);
+    }
+
+    #[test]
+    fn filmic_curves_start_at_zero_and_rise() {
+        for op in [ToneMap::Aces, ToneMap::Hable, ToneMap::Reinhard, ToneMap::ReinhardExtended { white: 4.0 }].iter() {
+            assert!(op.map(0.0).abs() < 1e-12, "{:?}", op);
+            let mut last = op.map(0.0);
+            for i in 1 .. 400 {
+                let y = op.map(i as f64 * 0.05);
+                assert!(y >= last, "{:?} falls at {}", op, i as f64 * 0.05);
+                last = y;
+            }
+            assert!(last > 0.8 && last <= 1.0 + 1e-9, "{:?} ends at {}", op, last);
+        }
+    }
+
+}
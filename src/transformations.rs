@@ -1,114 +1,518 @@
-use crate::tuples::Tuple4D;
-use crate::matrices::Mat;
+use crate::tuples::*;
+use crate::matrices::Mat4;
+use crate::quaternion::{Quaternion, EulerOrder};
 
+// points, vectors and normals each know how a transformation applies to them
+pub trait Transformable {
+    fn transformed(&self, transformation: &Transformation) -> Self;
+}
+
+impl Transformable for Point3 {
+    fn transformed(&self, transformation: &Transformation) -> Point3 {
+        let [x, y, z, _] = transformation.transformation.mul([self.x, self.y, self.z, 1.0]);
+        Point3::new(x, y, z)
+    }
+}
+
+impl Transformable for Vector3 {
+    fn transformed(&self, transformation: &Transformation) -> Vector3 {
+        let [x, y, z, _] = transformation.transformation.mul([self.x, self.y, self.z, 0.0]);
+        Vector3::new(x, y, z)
+    }
+}
+
+impl Transformable for Normal3 {
+    // normals go through the inverse transpose to stay perpendicular
+    // to the transformed surface, the result is not normalized
+    fn transformed(&self, transformation: &Transformation) -> Normal3 {
+        let inverse = transformation.inverse.expect("normal transformed by a singular transformation");
+        let [x, y, z, _] = inverse.mul_transposed([self.x, self.y, self.z, 0.0]);
+        Normal3::new(x, y, z)
+    }
+}
+
+// keeps its inverse around, so inverting and transforming normals is free
+#[derive(Clone, Debug)]
 pub struct Transformation {
-    pub transformation: Mat
+    pub transformation: Mat4,
+    inverse: Option<Mat4>
 }
 
 impl Transformation {
 
-    pub fn view(from: Tuple4D, to: Tuple4D, up: Tuple4D) -> Transformation {
-        let fwd = to.sub(&from).normalized();
+    pub fn new(transformation: Mat4) -> Transformation {
+        let inverse = transformation.inverse();
+        Transformation{transformation, inverse}
+    }
+
+    pub fn view(from: Point3, to: Point3, up: Vector3) -> Transformation {
+        let fwd = (to - from).normalized();
         let up_norm = up.normalized();
         let left = fwd.cross(&up_norm);
         let true_up = left.cross(&fwd);
-        let orientation = Transformation{ transformation: Mat::new(vec![
+        let orientation = Transformation::new(Mat4::new([
             left.x,    left.y,    left.z,    0.0,
             true_up.x, true_up.y, true_up.z, 0.0,
             -fwd.x,    -fwd.y,    -fwd.z,    0.0,
             0.0,       0.0,       0.0,       1.0
-        ], 4)
-        };
+        ]));
 
-        let trans = Transformation::translation(Tuple4D::new_point(-from.x, -from.y, -from.z));
-        Transformation::chain(&vec![orientation, trans])
+        let trans = Transformation::translation(Vector3::new(-from.x, -from.y, -from.z));
+        Transformation::chain(&[orientation, trans])
     }
 
     pub fn chain(transformations: &[Transformation]) -> Transformation {
-        let mut transformation = transformations[0].transformation.clone();
-        for i in 1 .. transformations.len() {
-            transformation = transformation.mat_mul(&transformations[i].transformation);
+        let mut transformation = transformations[0].transformation;
+        for t in transformations.iter().skip(1) {
+            transformation = transformation.mat_mul(&t.transformation);
         }
-        Transformation{transformation}
+        Transformation::new(transformation)
     }
 
     pub fn identity() -> Transformation {
-        let transformation = Mat::new(vec![
+        let transformation = Mat4::new([
             1.0, 0.0, 0.0, 0.0,
             0.0, 1.0, 0.0, 0.0,
             0.0, 0.0, 1.0, 0.0,
             0.0, 0.0, 0.0, 1.0
-        ], 4);
-        Transformation{transformation}
+        ]);
+        Transformation::new(transformation)
     }
 
-    pub fn translation(by: Tuple4D) -> Transformation {
-        let transformation = Mat::new(vec![
+    pub fn translation(by: Vector3) -> Transformation {
+        let transformation = Mat4::new([
             1.0, 0.0, 0.0, by.x,
             0.0, 1.0, 0.0, by.y,
             0.0, 0.0, 1.0, by.z,
             0.0, 0.0, 0.0, 1.0
-        ], 4);
-        Transformation{transformation}
+        ]);
+        Transformation::new(transformation)
     }
 
-    pub fn scale(by: Tuple4D) -> Transformation {
-        let transformation = Mat::new(vec![
+    pub fn scale(by: Vector3) -> Transformation {
+        let transformation = Mat4::new([
             by.x, 0.0, 0.0, 0.0,
             0.0, by.y, 0.0, 0.0,
             0.0, 0.0, by.z, 0.0,
             0.0, 0.0, 0.0,  1.0
-        ], 4);
-        Transformation{transformation}
+        ]);
+        Transformation::new(transformation)
     }
 
     pub fn rotate_x(by: f64) -> Transformation {
         let r = by.to_radians();
-        let transformation = Mat::new(vec![
+        let transformation = Mat4::new([
             1.0, 0.0,          0.0,         0.0,
             0.0, f64::cos(r), -f64::sin(r), 0.0,
             0.0, f64::sin(r),  f64::cos(r), 0.0,
             0.0, 0.0,          0.0,         1.0
-        ], 4);
-        Transformation{transformation}
+        ]);
+        Transformation::new(transformation)
     }
 
     pub fn rotate_y(by: f64) -> Transformation {
         let r = by.to_radians();
-        let transformation = Mat::new(vec![
+        let transformation = Mat4::new([
              f64::cos(r),  0.0,  f64::sin(r), 0.0,
              0.0,          1.0,  0.0,         0.0,
             -f64::sin(r),  0.0,  f64::cos(r), 0.0,
              0.0,          0.0,  0.0,         1.0
-        ], 4);
-        Transformation{transformation}
+        ]);
+        Transformation::new(transformation)
     }
 
     pub fn rotate_z(by: f64) -> Transformation {
         let r = by.to_radians();
-        let transformation = Mat::new(vec![
+        let transformation = Mat4::new([
              f64::cos(r), -f64::sin(r), 0.0, 0.0,
              f64::sin(r),  f64::cos(r), 0.0, 0.0,
              0.0,          0.0,         1.0, 0.0,
              0.0,          0.0,         0.0, 1.0
-        ], 4);
-        Transformation{transformation}
+        ]);
+        Transformation::new(transformation)
     }
 
-    pub fn inverse(&self) -> Option<Transformation> {
-        match self.transformation.inverse() {
-            None => None, 
-            Some(mat) => Some(Transformation{transformation: mat})
+    pub fn rotate_axis_angle(axis: Vector3, by: f64) -> Transformation {
+        Transformation::rotation(&Quaternion::from_axis_angle(&axis, by))
+    }
+
+    pub fn rotate_euler(x: f64, y: f64, z: f64, order: EulerOrder) -> Transformation {
+        Transformation::rotation(&Quaternion::from_euler(x, y, z, order))
+    }
+
+    pub fn rotation(q: &Quaternion) -> Transformation {
+        Transformation::new(q.to_mat())
+    }
+
+    // places an object at from with its -z axis pointing at to, the
+    // inverse of the view transformation when up is square to the line
+    // of sight
+    pub fn look_at(from: Point3, to: Point3, up: Vector3) -> Transformation {
+        let fwd = (to - from).normalized();
+        let left = fwd.cross(&up.normalized()).normalized();
+        let true_up = left.cross(&fwd);
+        let transformation = Mat4::new([
+            left.x, true_up.x, -fwd.x, from.x,
+            left.y, true_up.y, -fwd.y, from.y,
+            left.z, true_up.z, -fwd.z, from.z,
+            0.0,    0.0,       0.0,    1.0
+        ]);
+        Transformation::new(transformation)
+    }
+
+    // each factor moves one coordinate in proportion to another,
+    // xy moves x in proportion to y
+    pub fn shearing(xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Transformation {
+        let transformation = Mat4::new([
+            1.0, xy,  xz,  0.0,
+            yx,  1.0, yz,  0.0,
+            zx,  zy,  1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ]);
+        Transformation::new(transformation)
+    }
+
+    // mirrors across the plane through point with the given normal
+    pub fn mirror(point: Point3, normal: Vector3) -> Transformation {
+        let n = normal.normalized();
+        let reflection = Transformation::new(Mat4::new([
+            1.0 - 2.0 * n.x * n.x, -2.0 * n.x * n.y,       -2.0 * n.x * n.z,       0.0,
+            -2.0 * n.y * n.x,       1.0 - 2.0 * n.y * n.y, -2.0 * n.y * n.z,       0.0,
+            -2.0 * n.z * n.x,       -2.0 * n.z * n.y,       1.0 - 2.0 * n.z * n.z, 0.0,
+            0.0,                    0.0,                    0.0,                   1.0
+        ]));
+        Transformation::chain(&[
+            Transformation::translation(Vector3::new(point.x, point.y, point.z)),
+            reflection,
+            Transformation::translation(Vector3::new(-point.x, -point.y, -point.z))
+        ])
+    }
+
+    pub fn mirror_xy() -> Transformation {
+        Transformation::scale(Vector3::new(1.0, 1.0, -1.0))
+    }
+
+    pub fn mirror_xz() -> Transformation {
+        Transformation::scale(Vector3::new(1.0, -1.0, 1.0))
+    }
+
+    pub fn mirror_yz() -> Transformation {
+        Transformation::scale(Vector3::new(-1.0, 1.0, 1.0))
+    }
+
+    // applies self first and next afterwards, reads left to right
+    // unlike chain
+    pub fn then(&self, next: Transformation) -> Transformation {
+        Transformation::new(next.transformation.mat_mul(&self.transformation))
+    }
+
+    // splits into translation * rotation * scale, any shear is lost and
+    // a mirroring ends up as a negative x scale, none when an axis is
+    // flattened away and there is no rotation left to find
+    pub fn decompose(&self) -> Option<Decomposition> {
+        let m = &self.transformation;
+        let translation = Vector3::new(m.at(0, 3), m.at(1, 3), m.at(2, 3));
+        let columns: Vec<Vector3> = (0 .. 3)
+            .map(|j| Vector3::new(m.at(0, j), m.at(1, j), m.at(2, j)))
+            .collect();
+        let mut scale = Vector3::new(columns[0].magnitude(), columns[1].magnitude(), columns[2].magnitude());
+        let volume = columns[0].cross(&columns[1]).dot(&columns[2]);
+        if volume.abs() <= 1e-12 * scale.x * scale.y * scale.z {
+            return None;
         }
+        if volume < 0.0 {
+            scale.x = -scale.x;
+        }
+        let x = columns[0] * (1.0 / scale.x);
+        let y = columns[1] * (1.0 / scale.y);
+        let z = columns[2] * (1.0 / scale.z);
+        let rotation = Quaternion::from_mat(&Mat4::new([
+            x.x, y.x, z.x, 0.0,
+            x.y, y.y, z.y, 0.0,
+            x.z, y.z, z.z, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ]));
+        Some(Decomposition {translation, rotation, scale})
+    }
+
+    pub fn inverse(&self) -> Option<Transformation> {
+        self.inverse.map(|inverse| Transformation{transformation: inverse, inverse: Some(self.transformation)})
     }
 
     pub fn transpose(&self) -> Transformation {
-        Transformation {transformation: self.transformation.transpose()}
+        Transformation::new(self.transformation.transpose())
+    }
+
+    pub fn transform<T: Transformable>(&self, x: &T) -> T {
+        x.transformed(self)
+    }
+
+}
+
+#[derive(Clone, Debug)]
+pub struct Decomposition {
+    pub translation: Vector3,
+    pub rotation: Quaternion,
+    pub scale: Vector3
+}
+
+impl Decomposition {
+
+    pub fn transformation(&self) -> Transformation {
+        Transformation::scale(self.scale)
+            .then(Transformation::rotation(&self.rotation))
+            .then(Transformation::translation(self.translation))
+    }
+
+}
+
+#[derive(Clone, Debug)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translation: Vector3,
+    pub rotation: Quaternion,
+    pub scale: Vector3
+}
+
+impl Keyframe {
+
+    pub fn new(time: f64, translation: Vector3, rotation: Quaternion, scale: Vector3) -> Keyframe {
+        Keyframe {time, translation, rotation, scale}
+    }
+
+    pub fn from_transformation(time: f64, transformation: &Transformation) -> Option<Keyframe> {
+        transformation.decompose().map(|d| Keyframe::new(time, d.translation, d.rotation, d.scale))
+    }
+
+    // the inverse comes from the parts as S^-1 * R^-1 * T^-1 instead of a
+    // matrix inversion, motion blur builds one of these for every ray
+    pub fn transformation(&self) -> Transformation {
+        let t = self.translation;
+        let s = self.scale;
+        let transformation = Transformation::translation(t).transformation
+            .mat_mul(&self.rotation.to_mat())
+            .mat_mul(&Transformation::scale(s).transformation);
+        let inverse = if s.x == 0.0 || s.y == 0.0 || s.z == 0.0 {
+            None
+        } else {
+            let inverse = Transformation::scale(Vector3::new(1.0 / s.x, 1.0 / s.y, 1.0 / s.z)).transformation
+                .mat_mul(&self.rotation.conjugate().to_mat())
+                .mat_mul(&Transformation::translation(Vector3::new(-t.x, -t.y, -t.z)).transformation);
+            Some(inverse)
+        };
+        Transformation{transformation, inverse}
+    }
+
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimatedTransformation {
+    keyframes: Vec<Keyframe>
+}
+
+impl AnimatedTransformation {
+
+    pub fn new(keyframes: Vec<Keyframe>) -> AnimatedTransformation {
+        assert!(!keyframes.is_empty());
+        let mut keyframes = keyframes;
+        keyframes.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        AnimatedTransformation {keyframes}
     }
 
-    pub fn transform(&self, tuple: &Tuple4D) -> Tuple4D {
-        self.transformation.mul(tuple)
-    }    
+    pub fn at(&self, time: f64) -> Transformation {
+        let first = &self.keyframes[0];
+        let last = &self.keyframes[self.keyframes.len() - 1];
+        if time <= first.time {
+            return first.transformation();
+        }
+        if time >= last.time {
+            return last.transformation();
+        }
+        let next = self.keyframes.iter().position(|k| k.time > time).unwrap();
+        let a = &self.keyframes[next - 1];
+        let b = &self.keyframes[next];
+        let t = (time - a.time) / (b.time - a.time);
+        let lerp = |from: &Vector3, to: &Vector3| *from + (*to - *from) * t;
+        Keyframe::new(
+            time,
+            lerp(&a.translation, &b.translation),
+            a.rotation.slerp(&b.rotation, t),
+            lerp(&a.scale, &b.scale)
+        ).transformation()
+    }
 
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &Transformation, b: &Mat4) {
+        for i in 0 .. 4 {
+            for j in 0 .. 4 {
+                assert!((a.transformation.at(i, j) - b.at(i, j)).abs() < 1e-5, "{:?} != {:?}", a.transformation, b);
+            }
+        }
+    }
+
+    // checks the matrix and its inverse, and that the two undo each other
+    fn assert_matrix_and_inverse(t: &Transformation, m: &Mat4, inverse: &Mat4) {
+        assert_close(t, m);
+        assert_close(&t.inverse().unwrap(), inverse);
+        assert_close(&Transformation::new(m.mat_mul(inverse)), &Mat4::eye());
+    }
+
+    #[test]
+    fn shearing() {
+        let t = Transformation::shearing(1.0, 2.0, 3.0, 4.0, 5.0, 6.0);
+        let p = t.transform(&Point3::new(2.0, 3.0, 4.0));
+        assert_eq!((p.x, p.y, p.z), (13.0, 25.0, 32.0));
+        // a single factor is undone by its negative
+        assert_matrix_and_inverse(&Transformation::shearing(0.0, 0.0, 0.0, 0.0, 2.0, 0.0), &Mat4::new([
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            2.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ]), &Transformation::shearing(0.0, 0.0, 0.0, 0.0, -2.0, 0.0).transformation);
+    }
+
+    #[test]
+    fn mirrors_are_their_own_inverse() {
+        let above = Mat4::new([
+            1.0,  0.0, 0.0, 0.0,
+            0.0, -1.0, 0.0, 4.0,
+            0.0,  0.0, 1.0, 0.0,
+            0.0,  0.0, 0.0, 1.0
+        ]);
+        assert_matrix_and_inverse(&Transformation::mirror(Point3::new(5.0, 2.0, -1.0), Vector3::new(0.0, 3.0, 0.0)), &above, &above);
+        let diagonal = Mat4::new([
+             0.0, -1.0, 0.0, 0.0,
+            -1.0,  0.0, 0.0, 0.0,
+             0.0,  0.0, 1.0, 0.0,
+             0.0,  0.0, 0.0, 1.0
+        ]);
+        assert_matrix_and_inverse(&Transformation::mirror(Point3::origin(), Vector3::new(1.0, 1.0, 0.0)), &diagonal, &diagonal);
+        let xy = Transformation::mirror_xy().transformation;
+        assert_matrix_and_inverse(&Transformation::mirror(Point3::origin(), Vector3::new(0.0, 0.0, 1.0)), &xy, &xy);
+        let xz = Transformation::mirror_xz().transformation;
+        assert_matrix_and_inverse(&Transformation::mirror(Point3::origin(), Vector3::new(0.0, 1.0, 0.0)), &xz, &xz);
+        let yz = Transformation::mirror_yz().transformation;
+        assert_matrix_and_inverse(&Transformation::mirror(Point3::origin(), Vector3::new(1.0, 0.0, 0.0)), &yz, &yz);
+    }
+
+    #[test]
+    fn then_applies_self_first() {
+        let t = Transformation::translation(Vector3::new(1.0, 0.0, 0.0)).then(Transformation::scale(Vector3::new(2.0, 2.0, 2.0)));
+        assert_matrix_and_inverse(&t, &Mat4::new([
+            2.0, 0.0, 0.0, 2.0,
+            0.0, 2.0, 0.0, 0.0,
+            0.0, 0.0, 2.0, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ]), &Mat4::new([
+            0.5, 0.0, 0.0, -1.0,
+            0.0, 0.5, 0.0,  0.0,
+            0.0, 0.0, 0.5,  0.0,
+            0.0, 0.0, 0.0,  1.0
+        ]));
+        let p = t.transform(&Point3::new(1.0, 1.0, 1.0));
+        assert_eq!((p.x, p.y, p.z), (4.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn rotate_axis_angle() {
+        let quarter = Mat4::new([
+            0.0, -1.0, 0.0, 0.0,
+            1.0,  0.0, 0.0, 0.0,
+            0.0,  0.0, 1.0, 0.0,
+            0.0,  0.0, 0.0, 1.0
+        ]);
+        assert_matrix_and_inverse(&Transformation::rotate_axis_angle(Vector3::new(0.0, 0.0, 2.0), 90.0), &quarter, &quarter.transpose());
+        assert_close(&Transformation::rotate_z(90.0), &quarter);
+        // a third of a turn about the diagonal takes x to y, y to z and z to x
+        let cycle = Mat4::new([
+            0.0, 0.0, 1.0, 0.0,
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ]);
+        assert_matrix_and_inverse(&Transformation::rotate_axis_angle(Vector3::new(1.0, 1.0, 1.0), 120.0), &cycle, &cycle.transpose());
+    }
+
+    #[test]
+    fn look_at_undoes_view() {
+        let (from, to, up) = (Point3::origin(), Point3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        let turned = Mat4::new([
+            0.0, 0.0, -1.0, 0.0,
+            0.0, 1.0,  0.0, 0.0,
+            1.0, 0.0,  0.0, 0.0,
+            0.0, 0.0,  0.0, 1.0
+        ]);
+        assert_matrix_and_inverse(&Transformation::look_at(from, to, up), &turned, &turned.transpose());
+        assert_close(&Transformation::view(from, to, up), &turned.transpose());
+        // up square to the line of sight, view only normalizes then
+        let (from, to, up) = (Point3::new(1.0, 3.0, 2.0), Point3::new(3.0, 3.0, 3.0), Vector3::new(1.0, 1.0, -2.0));
+        let placed = Transformation::look_at(from, to, up);
+        assert_close(&placed.inverse().unwrap(), &Transformation::view(from, to, up).transformation);
+        let p = placed.transform(&Point3::origin());
+        assert!((p.x - 1.0).abs() < 1e-9 && (p.y - 3.0).abs() < 1e-9 && (p.z - 2.0).abs() < 1e-9);
+        let ahead = placed.transform(&Vector3::new(0.0, 0.0, -5.0_f64.sqrt()));
+        assert!((ahead.x - 2.0).abs() < 1e-9 && ahead.y.abs() < 1e-9 && (ahead.z - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn keyframe_inverse_from_parts() {
+        let rotation = Quaternion::from_euler(30.0, 45.0, 60.0, EulerOrder::XYZ);
+        let k = Keyframe::new(0.0, Vector3::new(1.0, -2.0, 3.0), rotation, Vector3::new(2.0, 0.5, 4.0));
+        let t = k.transformation();
+        assert_close(&t.inverse().unwrap(), &t.transformation.inverse().unwrap());
+        let flat = Keyframe::new(0.0, Vector3::new(0.0, 0.0, 0.0), Quaternion::identity(), Vector3::new(1.0, 0.0, 1.0));
+        assert!(flat.transformation().inverse().is_none());
+    }
+
+    #[test]
+    fn decompose_round_trip() {
+        let rotation = Quaternion::from_euler(20.0, -70.0, 110.0, EulerOrder::ZXY);
+        for scale in [Vector3::new(2.0, 0.5, 4.0), Vector3::new(1.0, -3.0, 0.25)].iter() {
+            let composed = Decomposition {translation: Vector3::new(1.0, -2.0, 3.0), rotation: rotation.clone(), scale: *scale}.transformation();
+            let d = composed.decompose().unwrap();
+            assert_close(&d.transformation(), &composed.transformation);
+            assert!((d.scale.x.abs() - scale.x.abs()).abs() < 1e-9 && (d.scale.y.abs() - scale.y.abs()).abs() < 1e-9);
+            let k = Keyframe::from_transformation(2.0, &composed).unwrap();
+            assert_close(&k.transformation(), &composed.transformation);
+        }
+        let flat = Transformation::scale(Vector3::new(1.0, 0.0, 1.0)).then(Transformation::rotate_y(30.0));
+        assert!(flat.decompose().is_none());
+        assert!(Keyframe::from_transformation(0.0, &flat).is_none());
+        assert!(Transformation::shearing(1.0, 0.0, 1.0, 0.0, 0.0, 0.0).decompose().is_none());
+    }
+
+    #[test]
+    fn view_default_orientation() {
+        let t = Transformation::view(Point3::origin(), Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 1.0, 0.0));
+        assert_close(&t, &Mat4::eye());
+    }
+
+    #[test]
+    fn view_looking_in_positive_z() {
+        let t = Transformation::view(Point3::origin(), Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0));
+        assert_close(&t, &Transformation::scale(Vector3::new(-1.0, 1.0, -1.0)).transformation);
+    }
+
+    #[test]
+    fn view_moves_the_world() {
+        let t = Transformation::view(Point3::new(0.0, 0.0, 8.0), Point3::origin(), Vector3::new(0.0, 1.0, 0.0));
+        assert_close(&t, &Transformation::translation(Vector3::new(0.0, 0.0, -8.0)).transformation);
+    }
+
+    #[test]
+    fn view_arbitrary() {
+        let t = Transformation::view(Point3::new(1.0, 3.0, 2.0), Point3::new(4.0, -2.0, 8.0), Vector3::new(1.0, 1.0, 0.0));
+        assert_close(&t, &Mat4::new([
+            -0.50709, 0.50709,  0.67612, -2.36643,
+             0.76772, 0.60609,  0.12122, -2.82843,
+            -0.35857, 0.59761, -0.71714,  0.00000,
+             0.00000, 0.00000,  0.00000,  1.00000
+        ]));
+    }
+
+}
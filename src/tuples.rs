@@ -1,93 +1,153 @@
-#[derive(Debug, Clone)]
-pub struct Tuple4D {
+use std::ops::{Add, Sub, Mul, Neg};
+
+// points, directions and surface normals are distinct types, so only
+// the combinations that make geometric sense compile
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point3 {
     pub x: f64,
     pub y: f64,
-    pub z: f64,
-    pub w: f64
+    pub z: f64
 }
 
-impl Tuple4D {
-    pub fn new(x: f64, y: f64, z: f64, w: f64) -> Tuple4D {
-        Tuple4D {x,y,z,w}
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vector3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Normal3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64
+}
+
+impl Point3 {
+    pub fn new(x: f64, y: f64, z: f64) -> Point3 {
+        Point3 {x, y, z}
     }
 
-    pub fn new_point(x: f64, y: f64, z: f64) -> Tuple4D {
-        let w = 1.0;
-        Tuple4D {x,y,z,w}
+    pub fn origin() -> Point3 {
+        Point3::new(0.0, 0.0, 0.0)
+    }
+}
+
+impl Vector3 {
+    pub fn new(x: f64, y: f64, z: f64) -> Vector3 {
+        Vector3 {x, y, z}
     }
 
-    pub fn new_vector(x: f64, y: f64, z: f64) -> Tuple4D {
-        let w = 0.0;
-        Tuple4D {x,y,z,w}
+    pub fn zero() -> Vector3 {
+        Vector3::new(0.0, 0.0, 0.0)
     }
 
-    pub fn is_point(&self) -> bool {
-        self.w == 1.0
+    pub fn magnitude(&self) -> f64 {
+        f64::sqrt(self.dot(self))
     }
 
-    pub fn is_vec(&self) -> bool {
-        self.w == 0.0
+    pub fn normalized(&self) -> Vector3 {
+        let mag = self.magnitude();
+        Vector3::new(self.x / mag, self.y / mag, self.z / mag)
     }
 
-    pub fn add(&self, other: &Tuple4D) -> Tuple4D {
-        let x = self.x + other.x;
-        let y = self.y + other.y;
-        let z = self.z + other.z;
-        let w = self.w + other.w;
-        Tuple4D {x,y,z,w}
+    pub fn dot(&self, other: &Vector3) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
     }
 
-    pub fn sub(&self, other: &Tuple4D) -> Tuple4D {
-        let x = self.x - other.x;
-        let y = self.y - other.y;
-        let z = self.z - other.z;
-        let w = self.w - other.w;
-        Tuple4D {x,y,z,w}
+    pub fn cross(&self, other: &Vector3) -> Vector3 {
+        let x = self.y * other.z - self.z * other.y;
+        let y = self.z * other.x - self.x * other.z;
+        let z = self.x * other.y - self.y * other.x;
+        Vector3::new(x, y, z)
     }
+}
 
-    pub fn scale(&self, scaler: f64) -> Tuple4D {
-        let x = self.x * scaler;
-        let y = self.y * scaler;
-        let z = self.z * scaler;
-        let w = self.w * scaler;
-        Tuple4D {x,y,z,w}
+impl Normal3 {
+    pub fn new(x: f64, y: f64, z: f64) -> Normal3 {
+        Normal3 {x, y, z}
     }
 
-    pub fn magnitude(&self) -> f64 {        
-        f64::sqrt(self.dot(self))
+    pub fn normalized(&self) -> Normal3 {
+        let n = self.to_vector().normalized();
+        Normal3::new(n.x, n.y, n.z)
     }
 
-    pub fn normalized(&self) -> Tuple4D {
-        let mag = self.magnitude();
-        let x = self.x / mag;
-        let y = self.y / mag;
-        let z = self.z / mag;
-        let w = self.w / mag;
-        Tuple4D {x,y,z,w}
+    pub fn dot(&self, other: &Vector3) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
     }
 
-    pub fn dot(&self, other: &Tuple4D) -> f64 {
-        assert!(self.is_vec() && other.is_vec());
-        let x = self.x * other.x;
-        let y = self.y * other.y;
-        let z = self.z * other.z;
-        x + y + z
+    pub fn to_vector(self) -> Vector3 {
+        Vector3::new(self.x, self.y, self.z)
     }
+}
 
-    pub fn col_dot(&self, other: &[f64]) -> f64 {
-        assert!(other.len() == 4);
-        let x = self.x * other[0];
-        let y = self.y * other[1];
-        let z = self.z * other[2];
-        let w = self.w * other[3];
-        x + y + z + w
+impl From<Vector3> for Normal3 {
+    fn from(v: Vector3) -> Normal3 {
+        Normal3::new(v.x, v.y, v.z)
     }
+}
 
-    pub fn cross(&self, other: &Tuple4D) -> Tuple4D {
-        assert!(self.is_vec() && other.is_vec());
-        let x = self.y * other.z - self.z * other.y;
-        let y = self.z * other.x - self.x * other.z;
-        let z = self.x * other.y - self.y * other.x;
-        Tuple4D::new_vector(x, y, z)
+impl Add<Vector3> for Point3 {
+    type Output = Point3;
+    fn add(self, other: Vector3) -> Point3 {
+        Point3::new(self.x + other.x, self.y + other.y, self.z + other.z)
     }
-}
\ No newline at end of file
+}
+
+impl Sub<Vector3> for Point3 {
+    type Output = Point3;
+    fn sub(self, other: Vector3) -> Point3 {
+        Point3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Sub<Point3> for Point3 {
+    type Output = Vector3;
+    fn sub(self, other: Point3) -> Vector3 {
+        Vector3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Add<Vector3> for Vector3 {
+    type Output = Vector3;
+    fn add(self, other: Vector3) -> Vector3 {
+        Vector3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl Sub<Vector3> for Vector3 {
+    type Output = Vector3;
+    fn sub(self, other: Vector3) -> Vector3 {
+        Vector3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Mul<f64> for Vector3 {
+    type Output = Vector3;
+    fn mul(self, scaler: f64) -> Vector3 {
+        Vector3::new(self.x * scaler, self.y * scaler, self.z * scaler)
+    }
+}
+
+impl Neg for Vector3 {
+    type Output = Vector3;
+    fn neg(self) -> Vector3 {
+        Vector3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl Mul<f64> for Normal3 {
+    type Output = Normal3;
+    fn mul(self, scaler: f64) -> Normal3 {
+        Normal3::new(self.x * scaler, self.y * scaler, self.z * scaler)
+    }
+}
+
+impl Neg for Normal3 {
+    type Output = Normal3;
+    fn neg(self) -> Normal3 {
+        Normal3::new(-self.x, -self.y, -self.z)
+    }
+}